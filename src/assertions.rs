@@ -93,6 +93,61 @@ pub fn generate_assertions() -> TokenStream {
             };
         }
 
+        /// Assert that a log containing `message` was captured at `level`
+        #[macro_export]
+        macro_rules! assert_logged {
+            ($app:expr, $level:expr, $message:expr) => {
+                {
+                    let level: bevy::log::Level = $level;
+                    let message: &str = $message;
+                    let logs = $app.captured_logs();
+                    assert!(
+                        logs.iter().any(|log| log.level == level && log.message.contains(message)),
+                        "Expected a {} log containing {:?}, captured logs:\n{}",
+                        level,
+                        message,
+                        logs.iter().map(|log| log.to_string()).collect::<Vec<_>>().join("\n")
+                    );
+                }
+            };
+        }
+
+        /// Assert that no log was captured at `ERROR` level
+        #[macro_export]
+        macro_rules! assert_no_errors_logged {
+            ($app:expr) => {
+                {
+                    let errors: Vec<String> = $app
+                        .captured_logs()
+                        .iter()
+                        .filter(|log| log.level == bevy::log::Level::ERROR)
+                        .map(|log| log.to_string())
+                        .collect();
+                    assert!(
+                        errors.is_empty(),
+                        "Expected no errors to be logged, found {}:\n{}",
+                        errors.len(),
+                        errors.join("\n")
+                    );
+                }
+            };
+        }
+
+        /// Assert that nothing changed since `snapshot` besides the listed component types.
+        ///
+        /// Spawned and despawned entities always count as changes.
+        #[macro_export]
+        macro_rules! assert_world_unchanged_except {
+            ($app:expr, $snapshot:expr $(, $component:ty)* $(,)?) => {
+                {
+                    let diff = $app
+                        .diff_since(&$snapshot)
+                        .except(&[$(std::any::type_name::<$component>()),*]);
+                    assert!(diff.is_empty(), "Expected the world to be unchanged. {}", diff);
+                }
+            };
+        }
+
         /// Assert that a query returns no results
         #[macro_export]
         macro_rules! assert_query_empty {
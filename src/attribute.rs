@@ -7,33 +7,104 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{parse::Parse, parse::ParseStream, ItemFn, Result};
 
-#[derive(Default)]
+use crate::dump::DumpLimit;
+use crate::schedule::{AmbiguityPolicy, Executor};
+
+#[derive(Default, Clone)]
 pub struct TestConfig {
-    pub headless: bool,
+    pub profile: TestProfile,
     pub plugins: Vec<syn::Expr>,
     pub timeout_ms: Option<u64>,
+    pub fixed_dt: Option<f64>,
+    pub states: Vec<syn::Expr>,
+    pub ambiguity: AmbiguityPolicy,
+    pub capture_logs: bool,
+    /// Executors to run the test on, one generated test each; empty keeps Bevy's default
+    pub executors: Vec<Executor>,
+    pub repeat: Option<u32>,
+    pub world_dump: DumpLimit,
+    /// Builds a resource from the seed of each `repeat` iteration
+    pub rng: Option<syn::Expr>,
+    /// Called with the configured app before the test body
+    pub setup: Option<syn::Expr>,
+    /// Called with the app after the test body, even when it panicked
+    pub teardown: Option<syn::Expr>,
+    pub cases: Vec<TestCase>,
+}
+
+/// Plugin set installed by `profile = ...`
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum TestProfile {
+    /// A bare `App` with only the main schedule
+    EcsOnly,
+    /// `MinimalPlugins`
+    #[default]
+    Minimal,
+    /// `MinimalPlugins` plus state, input, in-memory assets, transforms and a
+    /// virtual primary window, all without a GPU
+    HeadlessFull,
+}
+
+impl Parse for TestProfile {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "ecs_only" => Ok(TestProfile::EcsOnly),
+            "minimal" => Ok(TestProfile::Minimal),
+            "headless_full" => Ok(TestProfile::HeadlessFull),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "Unknown profile, expected `ecs_only`, `minimal` or `headless_full`",
+            )),
+        }
+    }
+}
+
+/// A single parametrized case, expanded into its own `#[test]`
+#[derive(Clone)]
+pub struct TestCase {
+    pub name: syn::Ident,
+    pub values: syn::Expr,
 }
 
 impl Parse for TestConfig {
     fn parse(input: ParseStream) -> Result<Self> {
-        parse_config(input)
+        parse_config(input, TestConfig::default())
     }
 }
 
-fn parse_config(input: ParseStream) -> Result<TestConfig> {
-    let mut config = TestConfig::default();
+/// Parse options on top of `config`; a list option replaces the inherited list
+/// instead of extending it.
+fn parse_config(input: ParseStream, mut config: TestConfig) -> Result<TestConfig> {
+    let mut replaced = std::collections::HashSet::new();
 
     while !input.is_empty() {
         let ident: syn::Ident = input.parse()?;
 
         match ident.to_string().as_str() {
+            "module" => {
+                // Defaults inserted by #[bevy_test_module]
+                let content;
+                syn::parenthesized!(content in input);
+                config = parse_config(&content, config)?;
+            }
             "headless" => {
-                config.headless = true;
+                config.profile = TestProfile::HeadlessFull;
+            }
+            "capture_logs" => {
+                config.capture_logs = true;
+            }
+            "profile" => {
+                input.parse::<syn::Token![=]>()?;
+                config.profile = input.parse()?;
             }
             "plugins" => {
                 input.parse::<syn::Token![=]>()?;
                 let content;
                 syn::bracketed!(content in input);
+                if replaced.insert("plugins") {
+                    config.plugins.clear();
+                }
                 while !content.is_empty() {
                     config.plugins.push(content.parse()?);
                     content.parse::<syn::Token![,]>().ok();
@@ -44,6 +115,81 @@ fn parse_config(input: ParseStream) -> Result<TestConfig> {
                 let lit: syn::LitInt = input.parse()?;
                 config.timeout_ms = Some(lit.base10_parse()?);
             }
+            "states" => {
+                input.parse::<syn::Token![=]>()?;
+                let content;
+                syn::bracketed!(content in input);
+                if replaced.insert("states") {
+                    config.states.clear();
+                }
+                while !content.is_empty() {
+                    config.states.push(content.parse()?);
+                    content.parse::<syn::Token![,]>().ok();
+                }
+            }
+            "ambiguity" => {
+                input.parse::<syn::Token![=]>()?;
+                config.ambiguity = input.parse()?;
+            }
+            "setup" => {
+                input.parse::<syn::Token![=]>()?;
+                config.setup = Some(input.parse()?);
+            }
+            "teardown" => {
+                input.parse::<syn::Token![=]>()?;
+                config.teardown = Some(input.parse()?);
+            }
+            "executors" => {
+                input.parse::<syn::Token![=]>()?;
+                config.executors = Executor::parse_list(input)?;
+            }
+            "repeat" => {
+                input.parse::<syn::Token![=]>()?;
+                let lit: syn::LitInt = input.parse()?;
+                let repeat = lit.base10_parse()?;
+                if repeat == 0 {
+                    return Err(syn::Error::new(lit.span(), "repeat must be at least 1"));
+                }
+                config.repeat = Some(repeat);
+            }
+            "rng" => {
+                input.parse::<syn::Token![=]>()?;
+                config.rng = Some(input.parse()?);
+            }
+            "world_dump" => {
+                input.parse::<syn::Token![=]>()?;
+                config.world_dump = input.parse()?;
+            }
+            "fixed_dt" => {
+                input.parse::<syn::Token![=]>()?;
+                let step: syn::Expr = input.parse()?;
+                config.fixed_dt = Some(crate::time::parse_seconds(&step)?);
+            }
+            "cases" => {
+                // cases(low = 10, high = 100)
+                let content;
+                syn::parenthesized!(content in input);
+                if replaced.insert("cases") {
+                    config.cases.clear();
+                }
+                while !content.is_empty() {
+                    let name = content.parse()?;
+                    content.parse::<syn::Token![=]>()?;
+                    let values = content.parse()?;
+                    config.cases.push(TestCase { name, values });
+                    content.parse::<syn::Token![,]>().ok();
+                }
+            }
+            "case" => {
+                // Unnamed cases are numbered in declaration order
+                input.parse::<syn::Token![=]>()?;
+                if replaced.insert("cases") {
+                    config.cases.clear();
+                }
+                let name = quote::format_ident!("case_{}", config.cases.len());
+                let values = input.parse()?;
+                config.cases.push(TestCase { name, values });
+            }
             _ => {
                 return Err(syn::Error::new(
                     ident.span(),
@@ -58,118 +204,723 @@ fn parse_config(input: ParseStream) -> Result<TestConfig> {
     Ok(config)
 }
 
+/// How a parameter of a #[bevy_test] function gets its value
+enum TestParam<'a> {
+    /// `&mut App` or `&mut TestApp`
+    App(&'a syn::PatType),
+    /// `&mut World`
+    World(&'a syn::PatType),
+    /// `#[fixture]` value built from the app
+    Fixture(&'a syn::PatType),
+    /// Value bound from `case = ...` or `cases(...)`
+    Case(&'a syn::PatType),
+}
+
 pub fn expand_bevy_test(config: TestConfig, function: ItemFn) -> TokenStream {
     let fn_name = &function.sig.ident;
+
+    if config.capture_logs && config.executors.contains(&Executor::MultiThreaded) {
+        return syn::Error::new_spanned(
+            fn_name,
+            "`capture_logs` runs systems on the test thread and cannot be combined with the multi-threaded executor",
+        )
+        .into_compile_error();
+    }
+
+    if config.timeout_ms.is_some() && config.executors.contains(&Executor::MultiThreaded) {
+        return syn::Error::new_spanned(
+            fn_name,
+            "`timeout` keeps systems on the test thread and cannot be combined with the multi-threaded executor",
+        )
+        .into_compile_error();
+    }
+
+    if let (Some(rng), None) = (&config.rng, config.repeat) {
+        return syn::Error::new_spanned(rng, "`rng` is seeded per iteration and requires `repeat`")
+            .into_compile_error();
+    }
+
+    // One test per case so a failure names the case that broke
+    let cases: Vec<_> = if config.cases.is_empty() {
+        vec![(fn_name.clone(), None)]
+    } else {
+        config
+            .cases
+            .iter()
+            .map(|case| {
+                (
+                    quote::format_ident!("{}_{}", fn_name, case.name),
+                    Some(&case.values),
+                )
+            })
+            .collect()
+    };
+
+    // ... and one per executor when the test runs on several
+    let variants: Vec<_> = match config.executors.as_slice() {
+        [] => cases
+            .into_iter()
+            .map(|(name, case)| (name, case, None))
+            .collect(),
+        [executor] => cases
+            .into_iter()
+            .map(|(name, case)| (name, case, Some(*executor)))
+            .collect(),
+        executors => cases
+            .into_iter()
+            .flat_map(|(name, case)| {
+                executors.iter().map(move |executor| {
+                    let name = quote::format_ident!("{}_{}", name, executor.suffix());
+                    (name, case, Some(*executor))
+                })
+            })
+            .collect(),
+    };
+
+    let expanded = classify_params(&function.sig, !config.cases.is_empty()).and_then(|params| {
+        variants
+            .iter()
+            .map(|(test_name, case, executor)| {
+                expand_test(&config, &function, &params, test_name, *case, *executor)
+            })
+            .collect()
+    });
+
+    expanded.unwrap_or_else(syn::Error::into_compile_error)
+}
+
+/// Apply the options of `#[bevy_test_module(...)]` as defaults to every #[bevy_test] in `module`
+pub fn expand_bevy_test_module(args: TokenStream, mut module: syn::ItemMod) -> TokenStream {
+    // Report bad options on the module instead of on every test
+    if let Err(error) = syn::parse2::<TestConfig>(args.clone()) {
+        return error.into_compile_error();
+    }
+    let Some((_, items)) = &mut module.content else {
+        return syn::Error::new_spanned(
+            &module,
+            "#[bevy_test_module] requires an inline module, e.g. `mod tests { ... }`",
+        )
+        .into_compile_error();
+    };
+
+    for item in items {
+        apply_module_defaults(item, &args);
+    }
+
+    quote! { #module }
+}
+
+/// Prefix the arguments of #[bevy_test] and nested #[bevy_test_module] attributes with
+/// `module(defaults)`, so the item's own options override the defaults
+fn apply_module_defaults(item: &mut syn::Item, defaults: &TokenStream) {
+    let (attrs, nested) = match item {
+        syn::Item::Fn(function) => (&mut function.attrs, None),
+        syn::Item::Mod(module) => (
+            &mut module.attrs,
+            module.content.as_mut().map(|(_, items)| items),
+        ),
+        _ => return,
+    };
+
+    let mut has_own_defaults = false;
+    for attr in attrs.iter_mut() {
+        let is_test = attr.path().segments.last().is_some_and(|segment| {
+            segment.ident == "bevy_test" || segment.ident == "bevy_test_module"
+        });
+        if !is_test {
+            continue;
+        }
+        has_own_defaults = true;
+
+        let path = attr.path().clone();
+        let own_args = match &attr.meta {
+            syn::Meta::List(list) => list.tokens.clone(),
+            _ => TokenStream::new(),
+        };
+        attr.meta = syn::parse_quote! { #path(module(#defaults), #own_args) };
+    }
+
+    // A nested #[bevy_test_module] passes the combined defaults on by itself
+    if let (Some(items), false) = (nested, has_own_defaults) {
+        for item in items {
+            apply_module_defaults(item, defaults);
+        }
+    }
+}
+
+/// Decide from its type how each parameter is injected
+fn classify_params(sig: &syn::Signature, has_cases: bool) -> Result<Vec<TestParam<'_>>> {
+    let mut params = Vec::new();
+
+    for arg in &sig.inputs {
+        let pat_type = match arg {
+            syn::FnArg::Typed(pat_type) => pat_type,
+            syn::FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "#[bevy_test] functions cannot take `self`",
+                ));
+            }
+        };
+
+        let is_fixture = pat_type
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("fixture"));
+
+        // Only references to the app are injected, other references can be case values
+        let borrowed = match &*pat_type.ty {
+            syn::Type::Reference(reference) => type_name(&reference.elem),
+            _ => None,
+        };
+
+        let param = match (&*pat_type.ty, borrowed.as_deref()) {
+            (syn::Type::Reference(reference), _) if is_fixture => {
+                return Err(syn::Error::new_spanned(
+                    reference,
+                    "#[fixture] parameters must be taken by value",
+                ));
+            }
+            (syn::Type::Reference(reference), Some(name @ ("App" | "TestApp" | "World"))) => {
+                if reference.mutability.is_none() {
+                    return Err(syn::Error::new_spanned(
+                        reference,
+                        "the test app must be borrowed mutably, e.g. `app: &mut App`",
+                    ));
+                }
+                if name == "World" {
+                    TestParam::World(pat_type)
+                } else {
+                    TestParam::App(pat_type)
+                }
+            }
+            _ if is_fixture => TestParam::Fixture(pat_type),
+            _ if has_cases => TestParam::Case(pat_type),
+            _ => return Err(unsupported_param(pat_type)),
+        };
+        params.push(param);
+    }
+
+    let mut borrows_app = params
+        .iter()
+        .filter_map(|param| match param {
+            TestParam::App(pat_type) | TestParam::World(pat_type) => Some(pat_type),
+            _ => None,
+        })
+        .skip(1);
+    if let Some(pat_type) = borrows_app.next() {
+        return Err(syn::Error::new_spanned(
+            pat_type,
+            "only one parameter can borrow the test app",
+        ));
+    }
+
+    Ok(params)
+}
+
+/// Name of the type behind a path, `dyn` or `impl` type, ignoring its module path
+fn type_name(ty: &syn::Type) -> Option<String> {
+    let path = match ty {
+        syn::Type::Path(type_path) => &type_path.path,
+        syn::Type::TraitObject(syn::TypeTraitObject { bounds, .. })
+        | syn::Type::ImplTrait(syn::TypeImplTrait { bounds, .. }) => match bounds.first()? {
+            syn::TypeParamBound::Trait(bound) => &bound.path,
+            _ => return None,
+        },
+        syn::Type::Paren(paren) => return type_name(&paren.elem),
+        _ => return None,
+    };
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+}
+
+fn unsupported_param(pat_type: &syn::PatType) -> syn::Error {
+    let pat = &pat_type.pat;
+    syn::Error::new_spanned(
+        pat_type,
+        format!(
+            "unsupported parameter `{}`: #[bevy_test] can inject `&mut App`, `&mut World`, \
+             `&mut TestApp` or a `#[fixture]` value, or bind it from `cases(...)`",
+            quote!(#pat)
+        ),
+    )
+}
+
+fn expand_test(
+    config: &TestConfig,
+    function: &ItemFn,
+    params: &[TestParam],
+    test_name: &syn::Ident,
+    case: Option<&syn::Expr>,
+    executor: Option<Executor>,
+) -> Result<TokenStream> {
+    let fn_attrs = &function.attrs;
+    let fn_vis = &function.vis;
+    let fn_output = &function.sig.output;
     let fn_body = &function.block;
-    let fn_args = &function.sig.inputs;
 
-    // Determine what the test function expects
-    let expects_app = fn_args.iter().any(|arg| {
-        if let syn::FnArg::Typed(pat_type) = arg {
-            // Check if parameter type contains "App" or "TestApp"
-            let type_str = quote!(#pat_type.ty).to_string();
-            type_str.contains("App") || type_str.contains("TestApp")
-        } else {
-            false
+    let case_params: Vec<_> = params
+        .iter()
+        .filter_map(|param| match param {
+            TestParam::Case(pat_type) => Some(*pat_type),
+            _ => None,
+        })
+        .collect();
+    let case_bindings = generate_case_bindings(&function.sig, &case_params, case)?;
+
+    // Fixtures are built once the app is configured, before the test borrows it
+    let fixture_bindings = params.iter().filter_map(|param| match param {
+        TestParam::Fixture(pat_type) => {
+            let pat = &pat_type.pat;
+            let ty = &pat_type.ty;
+            Some(quote! {
+                let #pat: #ty = <#ty as FromTestApp>::from_test_app(&mut app);
+            })
+        }
+        _ => None,
+    });
+    let fixture_bindings = quote! { #(#fixture_bindings)* };
+
+    let app_param = params.iter().find_map(|param| match param {
+        TestParam::App(pat_type) => {
+            let pat = &pat_type.pat;
+            Some((quote! { #pat: &mut bevy::app::App }, quote! { &mut app }))
+        }
+        TestParam::World(pat_type) => {
+            let pat = &pat_type.pat;
+            Some((
+                quote! { #pat: &mut bevy::ecs::world::World },
+                quote! { app.world_mut() },
+            ))
         }
+        _ => None,
     });
 
-    let setup_code = generate_setup(&config);
-    let timeout_code = generate_timeout(&config);
+    let setup_code = generate_setup(config, executor);
+
+    let returns = matches!(fn_output, syn::ReturnType::Type(..));
+    let test_code = if let Some((app_arg, app_value)) = app_param {
+        let run_test =
+            generate_guarded_run(config, quote! { test_fn(#app_value) }, returns, |code| {
+                quote! {{
+                    let app = &mut app;
+                    #code
+                }}
+            });
 
-    if expects_app {
         // Function expects an app parameter
         quote! {
-            #[test]
-            fn #fn_name() {
-                #timeout_code
+            #case_bindings
 
-                // Create and configure test app
-                let mut app = {
-                    #setup_code
-                };
+            // Create and configure test app
+            let mut app = {
+                #setup_code
+            };
 
-                // Define test function with app parameter
-                let test_fn = |app: &mut bevy::app::App| {
-                    #fn_body
-                };
+            #fixture_bindings
 
-                // Execute test
-                test_fn(&mut app);
-            }
+            // Define test function with app parameter
+            let test_fn = |#app_arg| #fn_output #fn_body;
+
+            // Execute test
+            #run_test
         }
     } else {
+        let run_body = generate_guarded_run(
+            config,
+            quote! { (|| #fn_output #fn_body)() },
+            returns,
+            |code| {
+                quote! { with_test_app(|app| #code) }
+            },
+        );
+
         // Function is self-contained
         quote! {
-            #[test]
-            fn #fn_name() {
-                #timeout_code
+            #case_bindings
 
-                // Create app in scope
-                let mut app = {
-                    #setup_code
-                };
+            // Create app in scope
+            let mut app = {
+                #setup_code
+            };
 
-                // Make app available via thread-local if needed
-                thread_local! {
-                    static TEST_APP: std::cell::RefCell<Option<bevy::app::App>> = std::cell::RefCell::new(None);
-                }
+            #fixture_bindings
+
+            // Make app available through `with_test_app` until the test returns
+            let _test_app_scope = TestAppScope::enter(app);
 
-                TEST_APP.with(|a| {
-                    *a.borrow_mut() = Some(app);
-                });
+            // Execute original test body
+            #run_body
+        }
+    };
 
-                // Execute original test body
-                #fn_body
+    let test_code = match config.repeat {
+        Some(repeat) => crate::repeat::generate_repeat(test_name, repeat, returns, test_code),
+        None => test_code,
+    };
 
-                // Clean up
-                TEST_APP.with(|a| {
-                    *a.borrow_mut() = None;
-                });
+    // A returned value has already been reported, so only its exit code is left. That
+    // is also all that can cross back from the supervised thread of a `timeout` test,
+    // since error types such as `Box<dyn Error>` are not `Send`
+    let fn_output = if returns {
+        quote! { -> std::process::ExitCode }
+    } else {
+        quote! {}
+    };
+    let test_code = generate_timeout(config, test_name, test_code);
+
+    Ok(quote! {
+        #(#fn_attrs)*
+        #[test]
+        #fn_vis fn #test_name() #fn_output {
+            #test_code
+        }
+    })
+}
+
+/// Run `test`; if it panics, print the world dump. Then run the configured teardown
+/// whether or not it panicked.
+///
+/// A test that `returns` a value is reported to an `ExitCode`, printing its captured
+/// logs when it failed, since a returned error does not reach the `LogCapture` drop.
+///
+/// `with_app` wraps code that uses `app: &mut App` so it can reach the test app.
+fn generate_guarded_run(
+    config: &TestConfig,
+    test: TokenStream,
+    returns: bool,
+    with_app: impl Fn(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let test = if returns {
+        let print_logs = with_app(quote! {
+            if let Some(capture) = app.world().get_resource::<LogCapture>() {
+                capture.print();
+            }
+        });
+        quote! {{
+            let code = std::process::Termination::report(#test);
+            if code != std::process::ExitCode::SUCCESS {
+                #print_logs
+            }
+            code
+        }}
+    } else {
+        test
+    };
+    let dump = config.world_dump.0.map(|limit| {
+        let dump = with_app(quote! { eprintln!("{}", dump_world(app.world(), #limit)) });
+        quote! {
+            if result.is_err() {
+                #dump;
             }
         }
+    });
+    let teardown = config
+        .teardown
+        .as_ref()
+        .map(|teardown| with_app(quote! { (#teardown)(app) }));
+    if dump.is_none() && teardown.is_none() {
+        return test;
+    }
+
+    quote! {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| #test));
+        #dump
+        #teardown;
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
     }
 }
 
-fn generate_setup(config: &TestConfig) -> TokenStream {
-    let plugins = if config.plugins.is_empty() {
+/// Bind the values of a case to the test's extra parameters
+fn generate_case_bindings(
+    sig: &syn::Signature,
+    params: &[&syn::PatType],
+    case: Option<&syn::Expr>,
+) -> Result<TokenStream> {
+    let Some(case) = case else {
+        return Ok(quote! {});
+    };
+
+    // A single parameter takes the whole value, several destructure a tuple
+    let values: Vec<&syn::Expr> = match case {
+        syn::Expr::Tuple(tuple) if params.len() != 1 => tuple.elems.iter().collect(),
+        _ => vec![case],
+    };
+
+    if params.is_empty() || values.len() != params.len() {
+        return Err(syn::Error::new_spanned(
+            case,
+            format!(
+                "case provides {} value(s) but `{}` takes {} case parameter(s)",
+                values.len(),
+                sig.ident,
+                params.len()
+            ),
+        ));
+    }
+
+    let bindings = params.iter().zip(values).map(|(param, value)| {
+        let pat = &param.pat;
+        let ty = &param.ty;
         quote! {
-            app.add_plugins(bevy::app::MinimalPlugins);
+            let #pat: #ty = #value;
         }
-    } else {
-        let plugins = &config.plugins;
+    });
+
+    Ok(quote! {
+        #(#bindings)*
+    })
+}
+
+fn generate_setup(config: &TestConfig, executor: Option<Executor>) -> TokenStream {
+    let profile = generate_profile(config.profile);
+    let plugins = &config.plugins;
+    let override_fixed = config.fixed_dt.is_some();
+    let time_control = crate::time::generate_time_control(
+        crate::time::step_nanos(config.fixed_dt.unwrap_or(crate::time::DEFAULT_FIXED_DT)),
+        quote! { #override_fixed },
+    );
+
+    // The watchdog is installed last so it sees every schedule the plugins added
+    let watchdog = if config.timeout_ms.is_some() {
         quote! {
-            app.add_plugins(bevy::app::MinimalPlugins);
-            #(app.add_plugins(#plugins);)*
+            __bevy_test_watchdog.install(&mut app);
         }
+    } else {
+        quote! {}
+    };
+
+    // A timed-out test is abandoned on its own thread, so its systems must not hold on to
+    // threads of the process-wide task pools that later tests need
+    let executor = match executor {
+        None if config.timeout_ms.is_some() => Some(Executor::SingleThreaded),
+        executor => executor,
     };
 
+    let ambiguity = crate::schedule::generate_ambiguity_policy(config.ambiguity);
+    let executor = crate::schedule::generate_executor(executor);
+    let seeded = config
+        .repeat
+        .map(|_| crate::repeat::generate_seeded_setup(config.rng.as_ref()));
+    let states = &config.states;
+    let setup_hook = config.setup.as_ref().map(|setup| {
+        quote! {
+            (#setup)(&mut app);
+        }
+    });
+
+    // Capture first so plugin setup logs are buffered too
+    let capture_logs = config.capture_logs.then(|| {
+        quote! {
+            TestApp::capture_logs(&mut app);
+        }
+    });
+
     quote! {
         let mut app = bevy::app::App::new();
-        #plugins
+        #capture_logs
+        #profile
+        #(app.add_plugins(#plugins);)*
 
         // Add common test resources
-        app.insert_resource(bevy::time::Time::default());
+        app.insert_resource(bevy::time::Time::<()>::default());
+        #time_control
+        #ambiguity
+        #executor
+        #seeded
 
-        // Configure for headless if needed
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            // Note: headless configuration would go here if needed
-        }
+        // Enter the initial states before the test body runs
+        #(TestApp::set_state(&mut app, #states);)*
+        #setup_hook
+
+        #watchdog
 
         app
     }
 }
 
-fn generate_timeout(config: &TestConfig) -> TokenStream {
+fn generate_profile(profile: TestProfile) -> TokenStream {
+    match profile {
+        TestProfile::EcsOnly => quote! {},
+        TestProfile::Minimal => quote! {
+            app.add_plugins(bevy::MinimalPlugins);
+        },
+        TestProfile::HeadlessFull => quote! {
+            app.add_plugins(bevy::MinimalPlugins);
+            app.add_plugins((
+                bevy::state::app::StatesPlugin,
+                bevy::input::InputPlugin,
+                bevy::transform::TransformPlugin,
+            ));
+
+            // Assets are served from memory; the source must exist before AssetPlugin
+            {
+                use bevy::asset::AssetApp;
+
+                let assets = TestAssets::default();
+                let root = assets.0.clone();
+                app.register_asset_source(
+                    bevy::asset::io::AssetSourceId::Default,
+                    bevy::asset::io::AssetSource::build().with_reader(move || {
+                        Box::new(bevy::asset::io::memory::MemoryAssetReader { root: root.clone() })
+                    }),
+                );
+                app.add_plugins(bevy::asset::AssetPlugin::default());
+                app.insert_resource(assets);
+            }
+
+            // A primary window entity that is never backed by a real window
+            app.add_plugins(bevy::window::WindowPlugin {
+                primary_window: Some(bevy::window::Window::default()),
+                exit_condition: bevy::window::ExitCondition::DontExit,
+                close_when_requested: false,
+            });
+        },
+    }
+}
+
+fn generate_timeout(
+    config: &TestConfig,
+    fn_name: &syn::Ident,
+    test_code: TokenStream,
+) -> TokenStream {
     if let Some(timeout_ms) = config.timeout_ms {
         quote! {
-            // Set test timeout
-            // Note: This would need platform-specific implementation
-            let _timeout = std::time::Duration::from_millis(#timeout_ms);
+            // Run the test on a supervised thread so a hung system fails the test
+            TestWatchdog::default().supervise(
+                stringify!(#fn_name),
+                std::time::Duration::from_millis(#timeout_ms),
+                move |__bevy_test_watchdog: TestWatchdog| {
+                    #test_code
+                },
+            )
         }
     } else {
-        quote! {}
+        test_code
+    }
+}
+
+/// Generate the watchdog that enforces `#[bevy_test(timeout = N)]`
+pub fn generate_watchdog() -> TokenStream {
+    quote! {
+        /// Watchdog backing `#[bevy_test(timeout = N)]`.
+        ///
+        /// Tracks the current frame and the schedule the app last entered so a
+        /// timed-out test can report where it was stuck.
+        #[derive(bevy::prelude::Resource, Clone, Default)]
+        pub struct TestWatchdog {
+            state: std::sync::Arc<TestWatchdogState>,
+        }
+
+        #[derive(Default)]
+        struct TestWatchdogState {
+            frame: std::sync::atomic::AtomicU64,
+            schedule: std::sync::Mutex<Option<bevy::ecs::schedule::InternedScheduleLabel>>,
+        }
+
+        /// Schedule that runs right before the wrapped schedule to record progress
+        #[derive(bevy::ecs::schedule::ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+        struct WatchdogCheckpoint(bevy::ecs::schedule::InternedScheduleLabel);
+
+        impl TestWatchdog {
+            /// Instrument every main and fixed schedule of the app with a checkpoint
+            pub fn install(&self, app: &mut bevy::app::App) {
+                use bevy::ecs::schedule::ScheduleLabel;
+
+                app.insert_resource(self.clone());
+
+                let mut checkpoints = Vec::new();
+                {
+                    let mut order = app.world_mut().resource_mut::<bevy::app::MainScheduleOrder>();
+                    order.startup_labels = Self::interleave(&order.startup_labels, &mut checkpoints, false);
+                    order.labels = Self::interleave(&order.labels, &mut checkpoints, true);
+                }
+                if let Some(mut order) = app.world_mut().get_resource_mut::<bevy::app::FixedMainScheduleOrder>() {
+                    order.labels = Self::interleave(&order.labels, &mut checkpoints, false);
+                }
+
+                for (label, starts_frame) in checkpoints {
+                    app.add_systems(
+                        WatchdogCheckpoint(label).intern(),
+                        move |watchdog: bevy::prelude::Res<TestWatchdog>| watchdog.enter(label, starts_frame),
+                    );
+                }
+            }
+
+            fn interleave(
+                labels: &[bevy::ecs::schedule::InternedScheduleLabel],
+                checkpoints: &mut Vec<(bevy::ecs::schedule::InternedScheduleLabel, bool)>,
+                first_starts_frame: bool,
+            ) -> Vec<bevy::ecs::schedule::InternedScheduleLabel> {
+                use bevy::ecs::schedule::ScheduleLabel;
+
+                let mut interleaved = Vec::with_capacity(labels.len() * 2);
+                for (index, &label) in labels.iter().enumerate() {
+                    checkpoints.push((label, first_starts_frame && index == 0));
+                    interleaved.push(WatchdogCheckpoint(label).intern());
+                    interleaved.push(label);
+                }
+                interleaved
+            }
+
+            fn enter(&self, label: bevy::ecs::schedule::InternedScheduleLabel, starts_frame: bool) {
+                if starts_frame {
+                    self.state.frame.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+                *self.state.schedule.lock().unwrap_or_else(|e| e.into_inner()) = Some(label);
+            }
+
+            /// Number of frames the app has started
+            pub fn frame(&self) -> u64 {
+                self.state.frame.load(std::sync::atomic::Ordering::SeqCst)
+            }
+
+            /// The schedule the app entered most recently
+            pub fn last_schedule(&self) -> Option<bevy::ecs::schedule::InternedScheduleLabel> {
+                *self.state.schedule.lock().unwrap_or_else(|e| e.into_inner())
+            }
+
+            /// Run `test` on its own thread and fail if it exceeds `timeout`.
+            ///
+            /// Panics raised by the test are re-raised unchanged, so `#[should_panic]`
+            /// keeps working. A test that times out is left running on its detached thread.
+            pub fn supervise<T, F>(self, test_name: &str, timeout: std::time::Duration, test: F) -> T
+            where
+                T: Send + 'static,
+                F: FnOnce(TestWatchdog) -> T + Send + 'static,
+            {
+                let (done_tx, done_rx) = std::sync::mpsc::channel();
+                let watchdog = self.clone();
+                let handle = std::thread::Builder::new()
+                    .name(test_name.to_string())
+                    .spawn(move || {
+                        let result = test(watchdog);
+                        let _ = done_tx.send(());
+                        result
+                    })
+                    .expect("failed to spawn test thread");
+
+                match done_rx.recv_timeout(timeout) {
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        let location = match self.last_schedule() {
+                            Some(schedule) => format!("frame {}, last schedule {:?}", self.frame(), schedule),
+                            None => "before the first schedule ran".to_string(),
+                        };
+                        panic!(
+                            "test `{}` exceeded its timeout of {} ms ({})",
+                            test_name,
+                            timeout.as_millis(),
+                            location
+                        );
+                    }
+                    _ => match handle.join() {
+                        Ok(result) => result,
+                        Err(panic) => std::panic::resume_unwind(panic),
+                    },
+                }
+            }
+        }
     }
 }
 
@@ -179,12 +930,83 @@ pub fn generate_test_helpers() -> TokenStream {
         /// Extension trait for TestApp functionality
         pub trait TestApp {
             fn spawn<B: bevy::ecs::bundle::Bundle>(&mut self, bundle: B) -> bevy::ecs::entity::Entity;
+            /// Spawn `bundle` with a `Name`, so it can be looked up with `entity` and `get`
+            fn spawn_named<B: bevy::ecs::bundle::Bundle>(&mut self, name: &str, bundle: B) -> bevy::ecs::entity::Entity;
+            /// The only entity whose `Name` is `name`
+            fn entity(&self, name: &str) -> bevy::ecs::entity::Entity;
+            /// Component `T` of the entity named `name`
+            fn get<T: bevy::ecs::component::Component>(&self, name: &str) -> &T;
+            /// Update until `Time<Virtual>` has advanced by exactly `seconds`
             fn advance_time(&mut self, seconds: f32);
+            /// Update until `Time<Virtual>` has advanced by exactly `duration`
+            fn advance_time_by(&mut self, duration: std::time::Duration);
+            /// Update until `FixedMain` has run exactly `ticks` more times
+            fn advance_fixed_ticks(&mut self, ticks: u32);
             fn advance_frames(&mut self, frames: usize);
+            /// Update until `predicate` holds, returning the number of updates it took.
+            ///
+            /// Fails with the limit once `max_frames` updates did not satisfy it.
+            fn run_until<F>(&mut self, predicate: F, max_frames: usize) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool;
+            /// Update while `predicate` holds, returning the number of updates it took
+            fn run_while<F>(&mut self, predicate: F, max_frames: usize) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool;
+            /// Like `run_until`, but gives up once `Time<Virtual>` advanced by `max_time`
+            fn run_until_for<F>(
+                &mut self,
+                predicate: F,
+                max_time: std::time::Duration,
+            ) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool;
+            /// Pause `Time<Virtual>`; updates keep running but virtual and fixed time stand still
+            fn pause_time(&mut self);
+            fn resume_time(&mut self);
+            /// Set how fast `Time<Virtual>` runs relative to the app's time step
+            fn set_time_speed(&mut self, speed: f32);
+            /// Run `system` once right away, apply its commands and return its output
+            fn run_system<O: 'static, M>(&mut self, system: impl bevy::ecs::system::IntoSystem<(), O, M>) -> O;
+            /// Like `run_system`, for systems that take an `In<T>` or other system input
+            fn run_system_with<I: bevy::ecs::system::SystemInput + 'static, O: 'static, M>(
+                &mut self,
+                system: impl bevy::ecs::system::IntoSystem<I, O, M>,
+                input: I::Inner<'_>,
+            ) -> O;
             fn send_event<E: bevy::ecs::event::Event>(&mut self, event: E);
             fn query<Q: bevy::ecs::query::QueryData>(&self) -> TestQuery<Q>;
-            fn resource<R: bevy::ecs::system::Resource>(&self) -> &R;
-            fn resource_mut<R: bevy::ecs::system::Resource>(&mut self) -> &mut R;
+            /// Query `Q` on entities that also match the filter `F`, e.g. `With<Enemy>`
+            fn query_filtered<Q: bevy::ecs::query::QueryData, F: bevy::ecs::query::QueryFilter>(
+                &self,
+            ) -> TestQuery<Q, F>;
+            fn resource<R: bevy::ecs::resource::Resource>(&self) -> &R;
+            fn resource_mut<R: bevy::ecs::resource::Resource>(&mut self) -> &mut R;
+            /// Transition to `state` right away, running its OnExit/OnEnter schedules
+            fn set_state<S: bevy::state::state::FreelyMutableState>(&mut self, state: S);
+            /// The current value of state `S`
+            fn current_state<S: bevy::state::state::States>(&self) -> S;
+            /// Start recording the transitions of state `S`
+            fn track_state<S: bevy::state::state::States>(&mut self);
+            /// Every transition of state `S` that ran OnExit/OnEnter since it was tracked
+            fn state_history<S: bevy::state::state::States>(&self) -> &[StateTransitionRecord<S>];
+            /// Record every entity, its components and their reflected fields
+            fn world_snapshot(&self) -> WorldSnapshot;
+            /// Entities and components that changed since `snapshot` was taken
+            fn diff_since(&self, snapshot: &WorldSnapshot) -> WorldDiff;
+            /// Start recording every `E` sent from now on, along with the frame of the update that read it
+            fn capture_events<E: bevy::ecs::event::Event + Clone>(&mut self);
+            /// Every `E` recorded since `capture_events`, oldest first
+            fn captured<E: bevy::ecs::event::Event + Clone>(&self) -> &[CapturedEvent<E>];
+            fn captured_count<E: bevy::ecs::event::Event + Clone>(&self) -> usize;
+            /// The most recently recorded `E`
+            fn last_captured<E: bevy::ecs::event::Event + Clone>(&self) -> Option<&E>;
+            /// Take the recorded events, leaving the capture running
+            fn drain_captured<E: bevy::ecs::event::Event + Clone>(&mut self) -> Vec<CapturedEvent<E>>;
+            /// Buffer this thread's logs instead of printing them; they are printed if the test fails
+            fn capture_logs(&mut self);
+            /// Every log recorded since `capture_logs`
+            fn captured_logs(&self) -> Vec<CapturedLog>;
         }
 
         impl TestApp for bevy::app::App {
@@ -192,10 +1014,72 @@ pub fn generate_test_helpers() -> TokenStream {
                 self.world_mut().spawn(bundle).id()
             }
 
+            fn spawn_named<B: bevy::ecs::bundle::Bundle>(&mut self, name: &str, bundle: B) -> bevy::ecs::entity::Entity {
+                let names = entity_names(self.world());
+                if names.iter().any(|existing| existing == name) {
+                    panic!("An entity named {:?} already exists; names in use: {:?}", name, names);
+                }
+                self.world_mut().spawn((bevy::ecs::name::Name::new(name.to_string()), bundle)).id()
+            }
+
+            fn entity(&self, name: &str) -> bevy::ecs::entity::Entity {
+                let matches: Vec<_> = self
+                    .world()
+                    .iter_entities()
+                    .filter(|entity| entity.get::<bevy::ecs::name::Name>().is_some_and(|n| n.as_str() == name))
+                    .map(|entity| entity.id())
+                    .collect();
+                match matches.as_slice() {
+                    [entity] => *entity,
+                    [] => panic!(
+                        "No entity named {:?}; existing names: {:?}",
+                        name,
+                        entity_names(self.world())
+                    ),
+                    _ => {
+                        let entities: Vec<_> = matches.iter().map(|entity| entity.to_string()).collect();
+                        panic!(
+                            "{} entities are named {:?}: {}; existing names: {:?}",
+                            matches.len(),
+                            name,
+                            entities.join(", "),
+                            entity_names(self.world())
+                        )
+                    }
+                }
+            }
+
+            fn get<T: bevy::ecs::component::Component>(&self, name: &str) -> &T {
+                let entity = self.entity(name);
+                match self.world().get::<T>(entity) {
+                    Some(component) => component,
+                    None => panic!(
+                        "Entity {:?} ({}) has no {} component",
+                        name,
+                        entity,
+                        std::any::type_name::<T>()
+                    ),
+                }
+            }
+
             fn advance_time(&mut self, seconds: f32) {
-                let frames = (seconds * 60.0) as usize;
-                for _ in 0..frames {
-                    self.update();
+                self.advance_time_by(duration_from_secs(seconds));
+            }
+
+            fn advance_time_by(&mut self, duration: std::time::Duration) {
+                advance_virtual_time(self, duration);
+            }
+
+            fn advance_fixed_ticks(&mut self, ticks: u32) {
+                // `FixedMain` runs once per timestep of accumulated virtual time, and the
+                // timestep can change between updates
+                for _ in 0..ticks {
+                    let fixed = self
+                        .world()
+                        .get_resource::<bevy::time::Time<bevy::time::Fixed>>()
+                        .expect("Advancing fixed time requires the TimePlugin");
+                    let duration = fixed.timestep() - fixed.overstep();
+                    self.advance_time_by(duration);
                 }
             }
 
@@ -205,6 +1089,68 @@ pub fn generate_test_helpers() -> TokenStream {
                 }
             }
 
+            fn run_until<F>(&mut self, predicate: F, max_frames: usize) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool,
+            {
+                RunLimit::Frames(max_frames).run(self, predicate)
+            }
+
+            fn run_while<F>(&mut self, mut predicate: F, max_frames: usize) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool,
+            {
+                RunLimit::Frames(max_frames).run(self, |world| !predicate(world))
+            }
+
+            fn run_until_for<F>(
+                &mut self,
+                predicate: F,
+                max_time: std::time::Duration,
+            ) -> Result<usize, RunLimitReached>
+            where
+                F: FnMut(&mut bevy::ecs::world::World) -> bool,
+            {
+                RunLimit::Time(max_time).run(self, predicate)
+            }
+
+            fn pause_time(&mut self) {
+                self.world_mut()
+                    .get_resource_mut::<bevy::time::Time<bevy::time::Virtual>>()
+                    .expect("Controlling time requires the TimePlugin")
+                    .pause();
+            }
+
+            fn resume_time(&mut self) {
+                self.world_mut()
+                    .get_resource_mut::<bevy::time::Time<bevy::time::Virtual>>()
+                    .expect("Controlling time requires the TimePlugin")
+                    .unpause();
+            }
+
+            fn set_time_speed(&mut self, speed: f32) {
+                self.world_mut()
+                    .get_resource_mut::<bevy::time::Time<bevy::time::Virtual>>()
+                    .expect("Controlling time requires the TimePlugin")
+                    .set_relative_speed(speed);
+            }
+
+            fn run_system<O: 'static, M>(&mut self, system: impl bevy::ecs::system::IntoSystem<(), O, M>) -> O {
+                self.run_system_with(system, ())
+            }
+
+            fn run_system_with<I: bevy::ecs::system::SystemInput + 'static, O: 'static, M>(
+                &mut self,
+                system: impl bevy::ecs::system::IntoSystem<I, O, M>,
+                input: I::Inner<'_>,
+            ) -> O {
+                use bevy::ecs::system::RunSystemOnce;
+
+                self.world_mut()
+                    .run_system_once_with(system, input)
+                    .unwrap_or_else(|error| panic!("{}", error))
+            }
+
             fn send_event<E: bevy::ecs::event::Event>(&mut self, event: E) {
                 self.world_mut().send_event(event);
             }
@@ -216,41 +1162,518 @@ pub fn generate_test_helpers() -> TokenStream {
                 }
             }
 
-            fn resource<R: bevy::ecs::system::Resource>(&self) -> &R {
+            fn query_filtered<Q: bevy::ecs::query::QueryData, F: bevy::ecs::query::QueryFilter>(
+                &self,
+            ) -> TestQuery<Q, F> {
+                TestQuery {
+                    world: self.world(),
+                    _phantom: std::marker::PhantomData,
+                }
+            }
+
+            fn resource<R: bevy::ecs::resource::Resource>(&self) -> &R {
                 self.world().resource::<R>()
             }
 
-            fn resource_mut<R: bevy::ecs::system::Resource>(&mut self) -> &mut R {
-                self.world_mut().resource_mut::<R>()
+            fn resource_mut<R: bevy::ecs::resource::Resource>(&mut self) -> &mut R {
+                self.world_mut().resource_mut::<R>().into_inner()
+            }
+
+            fn set_state<S: bevy::state::state::FreelyMutableState>(&mut self, state: S) {
+                use bevy::state::app::AppExtStates;
+
+                if !self.is_plugin_added::<bevy::state::app::StatesPlugin>() {
+                    self.add_plugins(bevy::state::app::StatesPlugin);
+                }
+                if self.world().contains_resource::<bevy::state::state::State<S>>() {
+                    self.world_mut()
+                        .resource_mut::<bevy::state::state::NextState<S>>()
+                        .set(state);
+                } else {
+                    self.insert_state(state);
+                }
+                self.track_state::<S>();
+
+                // Apply the transition now instead of at the next update
+                self.world_mut().run_schedule(bevy::state::state::StateTransition);
+            }
+
+            fn current_state<S: bevy::state::state::States>(&self) -> S {
+                self.world()
+                    .get_resource::<bevy::state::state::State<S>>()
+                    .unwrap_or_else(|| panic!("State {} is not initialized", std::any::type_name::<S>()))
+                    .get()
+                    .clone()
+            }
+
+            fn track_state<S: bevy::state::state::States>(&mut self) {
+                use bevy::ecs::schedule::IntoScheduleConfigs;
+
+                if self.world().contains_resource::<StateHistory<S>>() {
+                    return;
+                }
+                self.insert_resource(StateHistory::<S>(Vec::new()));
+                self.add_systems(
+                    bevy::state::state::StateTransition,
+                    StateHistory::<S>::record.after(bevy::state::state::StateTransitionSteps::EnterSchedules),
+                );
+            }
+
+            fn state_history<S: bevy::state::state::States>(&self) -> &[StateTransitionRecord<S>] {
+                match self.world().get_resource::<StateHistory<S>>() {
+                    Some(history) => &history.0,
+                    None => panic!(
+                        "State {} is not tracked; call `track_state` or `set_state` first",
+                        std::any::type_name::<S>()
+                    ),
+                }
+            }
+
+            fn world_snapshot(&self) -> WorldSnapshot {
+                WorldSnapshot::capture(self.world())
+            }
+
+            fn diff_since(&self, snapshot: &WorldSnapshot) -> WorldDiff {
+                snapshot.diff(&self.world_snapshot())
+            }
+
+            fn capture_events<E: bevy::ecs::event::Event + Clone>(&mut self) {
+                use bevy::ecs::schedule::IntoScheduleConfigs;
+
+                if self.world().contains_resource::<EventCapture<E>>() {
+                    return;
+                }
+                self.add_event::<E>();
+                self.insert_resource(EventCapture::<E>(Vec::new()));
+                // Before the frame count moves on, so events are stamped with the frame that read them
+                self.add_systems(
+                    bevy::app::Last,
+                    EventCapture::<E>::record.before(bevy::diagnostic::update_frame_count),
+                );
+            }
+
+            fn captured<E: bevy::ecs::event::Event + Clone>(&self) -> &[CapturedEvent<E>] {
+                match self.world().get_resource::<EventCapture<E>>() {
+                    Some(capture) => &capture.0,
+                    None => panic!(
+                        "Event {} is not captured; call `capture_events` first",
+                        std::any::type_name::<E>()
+                    ),
+                }
+            }
+
+            fn captured_count<E: bevy::ecs::event::Event + Clone>(&self) -> usize {
+                self.captured::<E>().len()
+            }
+
+            fn last_captured<E: bevy::ecs::event::Event + Clone>(&self) -> Option<&E> {
+                self.captured::<E>().last().map(|captured| &captured.event)
+            }
+
+            fn drain_captured<E: bevy::ecs::event::Event + Clone>(&mut self) -> Vec<CapturedEvent<E>> {
+                self.captured::<E>();
+                std::mem::take(&mut self.world_mut().resource_mut::<EventCapture<E>>().0)
+            }
+
+            fn capture_logs(&mut self) {
+                LogCapture::install(self);
+            }
+
+            fn captured_logs(&self) -> Vec<CapturedLog> {
+                self.world()
+                    .get_resource::<LogCapture>()
+                    .expect("Logs are not captured; use #[bevy_test(capture_logs)] or call `capture_logs` first")
+                    .logs()
+            }
+        }
+
+        /// How long `run_until` and its variants keep updating the app
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum RunLimit {
+            Frames(usize),
+            /// Elapsed `Time<Virtual>`
+            Time(std::time::Duration),
+        }
+
+        impl RunLimit {
+            fn run(
+                self,
+                app: &mut bevy::app::App,
+                mut done: impl FnMut(&mut bevy::ecs::world::World) -> bool,
+            ) -> Result<usize, RunLimitReached> {
+                use bevy::time::{Time, Virtual};
+
+                let virtual_elapsed = |app: &bevy::app::App| {
+                    app.world()
+                        .get_resource::<Time<Virtual>>()
+                        .expect("Running for a duration requires the TimePlugin")
+                        .elapsed()
+                };
+                let start = match self {
+                    RunLimit::Frames(_) => std::time::Duration::ZERO,
+                    RunLimit::Time(_) => virtual_elapsed(app),
+                };
+
+                let mut frames = 0;
+                let mut last = start;
+                let mut stalled = 0;
+                while !done(app.world_mut()) {
+                    match self {
+                        RunLimit::Frames(max_frames) if frames >= max_frames => {
+                            return Err(RunLimitReached { limit: self, frames });
+                        }
+                        RunLimit::Time(max_time) => {
+                            let elapsed = virtual_elapsed(app);
+                            if elapsed - start >= max_time {
+                                return Err(RunLimitReached { limit: self, frames });
+                            }
+                            // A paused clock would never reach the limit
+                            if frames > 0 && elapsed == last {
+                                stalled += 1;
+                                assert!(stalled < 2, "Time<Virtual> does not advance when the app updates");
+                            } else {
+                                stalled = 0;
+                            }
+                            last = elapsed;
+                        }
+                        _ => {}
+                    }
+                    app.update();
+                    frames += 1;
+                }
+                Ok(frames)
+            }
+        }
+
+        /// `run_until` or `run_while` hit its limit before the condition was met
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct RunLimitReached {
+            pub limit: RunLimit,
+            /// Updates that ran before giving up
+            pub frames: usize,
+        }
+
+        impl std::fmt::Display for RunLimitReached {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.limit {
+                    RunLimit::Frames(max_frames) => {
+                        write!(f, "Condition not met within the limit of {} frames", max_frames)
+                    }
+                    RunLimit::Time(max_time) => write!(
+                        f,
+                        "Condition not met within the limit of {:?} of virtual time ({} frames)",
+                        max_time, self.frames
+                    ),
+                }
+            }
+        }
+
+        impl std::error::Error for RunLimitReached {}
+
+        /// `seconds` as a `Duration`, going through the shortest decimal form so `0.1` is
+        /// 100 ms rather than the f32 closest to it
+        fn duration_from_secs(seconds: f32) -> std::time::Duration {
+            let seconds: f64 = seconds.to_string().parse().unwrap_or(seconds as f64);
+            assert!(seconds.is_finite() && seconds >= 0.0, "Cannot advance time by {} seconds", seconds);
+            std::time::Duration::from_secs_f64(seconds)
+        }
+
+        /// Update until `Time<Virtual>` has advanced by exactly `duration`, returning the
+        /// number of updates. Updates are full steps except for a shorter last one when
+        /// `duration` is not a whole number of steps
+        fn advance_virtual_time(app: &mut bevy::app::App, duration: std::time::Duration) -> usize {
+            use bevy::time::{Time, TimeUpdateStrategy, Virtual};
+
+            if !matches!(
+                app.world().get_resource::<TimeUpdateStrategy>(),
+                Some(TimeUpdateStrategy::ManualDuration(_))
+            ) {
+                app.insert_resource(TimeUpdateStrategy::ManualDuration(std::time::Duration::from_secs_f64(1.0 / 60.0)));
+            }
+            let virtual_time = app
+                .world()
+                .get_resource::<Time<Virtual>>()
+                .expect("Advancing time requires the TimePlugin");
+            assert!(
+                !virtual_time.is_paused(),
+                "Cannot advance virtual time while it is paused; call `resume_time` first"
+            );
+            let speed = virtual_time.relative_speed_f64();
+            let target = virtual_time.elapsed() + duration;
+
+            // `Time<Virtual>` scales real time the same way
+            let scaled = |real: std::time::Duration| if speed == 1.0 { real } else { real.mul_f64(speed) };
+
+            let mut updates = 0;
+            let mut stalled = 0;
+            loop {
+                let elapsed = app.world().resource::<Time<Virtual>>().elapsed();
+                if elapsed >= target {
+                    break;
+                }
+
+                // The step of the next update, which can differ by a nanosecond between updates
+                let step = match app.world().resource::<TimeUpdateStrategy>() {
+                    TimeUpdateStrategy::ManualDuration(step) => *step,
+                    _ => unreachable!(),
+                };
+                let remaining = target - elapsed;
+                if scaled(step) > remaining {
+                    // Real time that covers exactly the rest, or as little more as possible
+                    let real = remaining.as_nanos() as f64 / speed;
+                    let needed = [real.floor(), real.ceil()]
+                        .map(|nanos| std::time::Duration::from_nanos(nanos as u64))
+                        .into_iter()
+                        .find(|&needed| scaled(needed) == remaining)
+                        .unwrap_or(std::time::Duration::from_nanos(real.ceil() as u64));
+                    app.insert_resource(TimeUpdateStrategy::ManualDuration(needed));
+                    app.update();
+                    app.insert_resource(TimeUpdateStrategy::ManualDuration(step));
+                } else {
+                    app.update();
+                }
+                updates += 1;
+
+                // The very first update of an app does not advance time
+                if app.world().resource::<Time<Virtual>>().elapsed() == elapsed {
+                    stalled += 1;
+                    assert!(stalled < 2, "Time<Virtual> does not advance when the app updates");
+                } else {
+                    stalled = 0;
+                }
+            }
+            updates
+        }
+
+        /// Sorted `Name`s of all entities in `world`
+        fn entity_names(world: &bevy::ecs::world::World) -> Vec<String> {
+            let mut names: Vec<_> = world
+                .iter_entities()
+                .filter_map(|entity| entity.get::<bevy::ecs::name::Name>().map(|name| name.to_string()))
+                .collect();
+            names.sort();
+            names
+        }
+
+        /// A state transition that ran OnExit for `exited` and OnEnter for `entered`
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct StateTransitionRecord<S> {
+            /// Value of `FrameCount` when the transition ran
+            pub frame: u32,
+            pub exited: Option<S>,
+            pub entered: Option<S>,
+        }
+
+        #[derive(bevy::prelude::Resource)]
+        struct StateHistory<S: bevy::state::state::States>(Vec<StateTransitionRecord<S>>);
+
+        impl<S: bevy::state::state::States> StateHistory<S> {
+            fn record(
+                mut transitions: bevy::prelude::EventReader<bevy::state::state::StateTransitionEvent<S>>,
+                frame: Option<bevy::prelude::Res<bevy::diagnostic::FrameCount>>,
+                mut history: bevy::prelude::ResMut<Self>,
+            ) {
+                let frame = frame.map_or(0, |frame| frame.0);
+                for transition in transitions.read() {
+                    // Identity transitions do not run OnExit/OnEnter
+                    if transition.exited != transition.entered {
+                        history.0.push(StateTransitionRecord {
+                            frame,
+                            exited: transition.exited.clone(),
+                            entered: transition.entered.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        /// An event recorded by `TestApp::capture_events`
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct CapturedEvent<E> {
+            /// Value of `FrameCount` in the update that read the event, which is the number
+            /// of updates completed before that update
+            pub frame: u32,
+            pub event: E,
+        }
+
+        #[derive(bevy::prelude::Resource)]
+        struct EventCapture<E: bevy::ecs::event::Event + Clone>(Vec<CapturedEvent<E>>);
+
+        impl<E: bevy::ecs::event::Event + Clone> EventCapture<E> {
+            fn record(
+                mut events: bevy::prelude::EventReader<E>,
+                frame: Option<bevy::prelude::Res<bevy::diagnostic::FrameCount>>,
+                mut capture: bevy::prelude::ResMut<Self>,
+            ) {
+                let frame = frame.map_or(0, |frame| frame.0);
+                capture.0.extend(events.read().map(|event| CapturedEvent {
+                    frame,
+                    event: event.clone(),
+                }));
+            }
+        }
+
+        thread_local! {
+            static CURRENT_TEST_APP: std::cell::RefCell<Option<bevy::app::App>> =
+                const { std::cell::RefCell::new(None) };
+        }
+
+        /// Run `f` with the app of the #[bevy_test] running on this thread.
+        ///
+        /// Available in tests that do not take the app as a parameter, so helpers deep
+        /// inside a test can reach the app without it being passed down.
+        ///
+        /// # Panics
+        ///
+        /// Panics outside such a test, or when called from within another `with_test_app`.
+        pub fn with_test_app<R>(f: impl FnOnce(&mut bevy::app::App) -> R) -> R {
+            CURRENT_TEST_APP.with(|current| {
+                let mut current = current
+                    .try_borrow_mut()
+                    .expect("with_test_app cannot be called from within another with_test_app");
+                let app = current.as_mut().expect(
+                    "with_test_app called outside of a #[bevy_test] function without app parameters",
+                );
+                f(app)
+            })
+        }
+
+        /// Keeps the app reachable through `with_test_app` while in scope
+        #[doc(hidden)]
+        pub struct TestAppScope(());
+
+        impl TestAppScope {
+            pub fn enter(app: bevy::app::App) -> Self {
+                CURRENT_TEST_APP.with(|current| *current.borrow_mut() = Some(app));
+                TestAppScope(())
+            }
+        }
+
+        impl Drop for TestAppScope {
+            fn drop(&mut self) {
+                // Take the app out first so its own drop cannot observe the thread-local
+                let app = CURRENT_TEST_APP.with(|current| current.borrow_mut().take());
+                drop(app);
+            }
+        }
+
+        /// In-memory asset directory served by the `headless_full` profile
+        #[derive(bevy::prelude::Resource, Clone, Default)]
+        pub struct TestAssets(pub bevy::asset::io::memory::Dir);
+
+        impl TestAssets {
+            /// Make `bytes` loadable from `path` through the `AssetServer`
+            pub fn insert(&self, path: impl AsRef<std::path::Path>, bytes: impl Into<Vec<u8>>) {
+                self.0.insert_asset(path.as_ref(), bytes.into());
+            }
+
+            /// Make `text` loadable from `path` through the `AssetServer`
+            pub fn insert_text(&self, path: impl AsRef<std::path::Path>, text: &str) {
+                self.0.insert_asset_text(path.as_ref(), text);
             }
         }
 
         /// Query wrapper for testing
-        pub struct TestQuery<'w, Q: bevy::ecs::query::QueryData> {
+        pub struct TestQuery<'w, Q: bevy::ecs::query::QueryData, F: bevy::ecs::query::QueryFilter = ()> {
             world: &'w bevy::ecs::world::World,
-            _phantom: std::marker::PhantomData<Q>,
+            _phantom: std::marker::PhantomData<(Q, F)>,
         }
 
-        impl<'w, Q: bevy::ecs::query::QueryData> TestQuery<'w, Q> {
-            pub fn single(&self) -> Q::Item<'w> {
-                let mut query = self.world.query::<Q>();
-                query.single(self.world)
+        impl<'w, Q: bevy::ecs::query::QueryData, F: bevy::ecs::query::QueryFilter> TestQuery<'w, Q, F> {
+            pub fn single(&self) -> bevy::ecs::query::ROQueryItem<'w, Q> {
+                self.try_single().unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            /// The only match, or an error with the number of matches and their entities
+            pub fn try_single(&self) -> Result<bevy::ecs::query::ROQueryItem<'w, Q>, QuerySingleError> {
+                let mut items: Vec<_> = self.iter_with_entity().collect();
+                if items.len() == 1 {
+                    return Ok(items.pop().unwrap().1);
+                }
+                Err(QuerySingleError {
+                    query: std::any::type_name::<Q>(),
+                    entities: items.into_iter().map(|(entity, _)| entity).collect(),
+                })
+            }
+
+            /// The item of `entity`, if it matches the query
+            pub fn get(&self, entity: bevy::ecs::entity::Entity) -> Option<bevy::ecs::query::ROQueryItem<'w, Q>> {
+                let mut query = self.world.try_query_filtered::<Q, F>()?;
+                query.get(self.world, entity).ok()
+            }
+
+            /// The first item matching `predicate`
+            pub fn find(
+                &self,
+                mut predicate: impl FnMut(&bevy::ecs::query::ROQueryItem<'w, Q>) -> bool,
+            ) -> Option<bevy::ecs::query::ROQueryItem<'w, Q>> {
+                self.iter().find(|item| predicate(item))
+            }
+
+            /// All items, sorted with `compare` since query order is not meaningful
+            pub fn collect_sorted_by(
+                &self,
+                mut compare: impl FnMut(
+                    &bevy::ecs::query::ROQueryItem<'w, Q>,
+                    &bevy::ecs::query::ROQueryItem<'w, Q>,
+                ) -> std::cmp::Ordering,
+            ) -> Vec<bevy::ecs::query::ROQueryItem<'w, Q>> {
+                let mut items: Vec<_> = self.iter().collect();
+                items.sort_by(|a, b| compare(a, b));
+                items
             }
 
             pub fn is_empty(&self) -> bool {
-                let mut query = self.world.query::<Q>();
-                query.iter(self.world).count() == 0
+                self.count() == 0
             }
 
             pub fn count(&self) -> usize {
-                let mut query = self.world.query::<Q>();
-                query.iter(self.world).count()
+                self.iter().count()
+            }
+
+            pub fn iter(&self) -> impl Iterator<Item = bevy::ecs::query::ROQueryItem<'w, Q>> {
+                self.iter_with_entity().map(|(_, item)| item)
             }
 
-            pub fn iter(&self) -> impl Iterator<Item = Q::Item<'w>> {
-                let mut query = self.world.query::<Q>();
-                query.iter(self.world)
+            fn iter_with_entity(
+                &self,
+            ) -> impl Iterator<Item = (bevy::ecs::entity::Entity, bevy::ecs::query::ROQueryItem<'w, Q>)> {
+                // Components that were never registered cannot match anything
+                let items: Vec<_> = match self.world.try_query_filtered::<(bevy::ecs::entity::Entity, Q), F>() {
+                    Some(mut query) => query.iter(self.world).collect(),
+                    None => Vec::new(),
+                };
+                items.into_iter()
             }
         }
+
+        /// `TestQuery::try_single` matched no entity or several
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct QuerySingleError {
+            /// Type name of the queried data
+            pub query: &'static str,
+            /// Every entity that matched
+            pub entities: Vec<bevy::ecs::entity::Entity>,
+        }
+
+        impl std::fmt::Display for QuerySingleError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.entities.as_slice() {
+                    [] => write!(f, "Expected exactly one match for query {}, found none", self.query),
+                    entities => {
+                        let entities: Vec<_> = entities.iter().map(|entity| entity.to_string()).collect();
+                        write!(
+                            f,
+                            "Expected exactly one match for query {}, found {}: {}",
+                            self.query,
+                            entities.len(),
+                            entities.join(", ")
+                        )
+                    }
+                }
+            }
+        }
+
+        impl std::error::Error for QuerySingleError {}
     }
 }
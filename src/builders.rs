@@ -8,6 +8,10 @@ use quote::quote;
 
 /// Generate MockWorld builder implementation
 pub fn generate_mock_world() -> TokenStream {
+    let default_step = crate::time::step_nanos(crate::time::DEFAULT_FIXED_DT);
+    let time_control =
+        crate::time::generate_time_control(quote! { step_nanos }, quote! { override_fixed });
+
     quote! {
         /// Builder for creating test worlds with entities and components
         pub struct MockWorld {
@@ -18,10 +22,21 @@ pub fn generate_mock_world() -> TokenStream {
             /// Create a new mock world
             pub fn new() -> Self {
                 let mut app = bevy::app::App::new();
-                app.add_plugins(bevy::app::MinimalPlugins);
+                app.add_plugins(bevy::MinimalPlugins);
+                Self::set_time_step(&mut app, #default_step, false);
                 Self { app }
             }
 
+            /// Advance the clocks by `step` on every update instead of the default 1/60 s
+            pub fn with_time_step(mut self, step: std::time::Duration) -> Self {
+                Self::set_time_step(&mut self.app, step.as_nanos() as f64, true);
+                self
+            }
+
+            fn set_time_step(app: &mut bevy::app::App, step_nanos: f64, override_fixed: bool) {
+                #time_control
+            }
+
             /// Add a specific number of entities to the world
             pub fn with_entities(mut self, count: usize) -> Self {
                 for _ in 0..count {
@@ -32,7 +47,7 @@ pub fn generate_mock_world() -> TokenStream {
 
             /// Add entities with random components of a specific type
             pub fn with_random_components<T: bevy::prelude::Component + Default>(mut self) -> Self {
-                let entities: Vec<_> = self.app.world()
+                let entities: Vec<_> = self.app.world_mut()
                     .query::<bevy::prelude::Entity>()
                     .iter(self.app.world())
                     .collect();
@@ -68,104 +83,520 @@ pub fn generate_mock_world() -> TokenStream {
 /// Generate MockInput builder implementation
 pub fn generate_mock_input() -> TokenStream {
     quote! {
-        /// Builder for simulating input events in tests
+        /// Builder for simulating input events in tests.
+        ///
+        /// Events are placed on a timeline that starts when the input is applied. Events
+        /// added one after another go into the same frame; `at_frame`, `at_time` and `wait`
+        /// move the following events to a later frame.
         pub struct MockInput {
-            events: Vec<InputEvent>,
+            steps: Vec<InputStep>,
+            /// Logical size of the window spawned when the app has no primary window
+            window_size: bevy::math::Vec2,
+        }
+
+        enum InputStep {
+            Event(InputEvent),
+            /// Frames since the input was applied
+            AtFrame(u32),
+            /// Virtual time since the input was applied
+            AtTime(std::time::Duration),
+            /// Virtual time since the frame of the previous events
+            Wait(std::time::Duration),
         }
 
         enum InputEvent {
             KeyPress(bevy::prelude::KeyCode),
             KeyRelease(bevy::prelude::KeyCode),
             MouseMove(bevy::math::Vec2),
-            MouseClick(bevy::input::mouse::MouseButton),
-            Wait(f32),
+            MouseMotion(bevy::math::Vec2),
+            MouseWheel(bevy::input::mouse::MouseScrollUnit, bevy::math::Vec2),
+            WindowResize(bevy::math::Vec2),
+            WindowFocus(bool),
+            WindowScaleFactor(f32),
+            MousePress(bevy::input::mouse::MouseButton),
+            MouseRelease(bevy::input::mouse::MouseButton),
+            GamepadConnect(usize),
+            GamepadDisconnect(usize),
+            GamepadButton(usize, bevy::input::gamepad::GamepadButton, f32),
+            GamepadAxis(usize, bevy::input::gamepad::GamepadAxis, f32),
+        }
+
+        impl InputEvent {
+            /// Whether both events change the state of the same key or button
+            fn same_button(&self, other: &InputEvent) -> bool {
+                use InputEvent::*;
+
+                match (self, other) {
+                    (KeyPress(a) | KeyRelease(a), KeyPress(b) | KeyRelease(b)) => a == b,
+                    (MousePress(a) | MouseRelease(a), MousePress(b) | MouseRelease(b)) => a == b,
+                    (GamepadConnect(a) | GamepadDisconnect(a), GamepadConnect(b) | GamepadDisconnect(b)) => a == b,
+                    (GamepadButton(a, x, _), GamepadButton(b, y, _)) => a == b && x == y,
+                    (GamepadAxis(a, x, _), GamepadAxis(b, y, _)) => a == b && x == y,
+                    _ => false,
+                }
+            }
         }
 
         impl MockInput {
             /// Create a new mock input sequence
             pub fn new() -> Self {
-                Self { events: Vec::new() }
+                Self {
+                    steps: Vec::new(),
+                    window_size: bevy::math::Vec2::new(1280.0, 720.0),
+                }
             }
 
-            /// Simulate pressing a key
-            pub fn press(mut self, key: bevy::prelude::KeyCode) -> Self {
-                self.events.push(InputEvent::KeyPress(key));
+            /// Logical size of the virtual window created when the app has no primary window
+            pub fn window_size(mut self, width: f32, height: f32) -> Self {
+                self.window_size = bevy::math::Vec2::new(width, height);
                 self
             }
 
-            /// Simulate releasing a key
-            pub fn release(mut self, key: bevy::prelude::KeyCode) -> Self {
-                self.events.push(InputEvent::KeyRelease(key));
+            fn event(mut self, event: InputEvent) -> Self {
+                self.steps.push(InputStep::Event(event));
                 self
             }
 
-            /// Simulate moving the mouse
-            pub fn mouse_move(mut self, position: bevy::math::Vec2) -> Self {
-                self.events.push(InputEvent::MouseMove(position));
+            /// Simulate pressing a key
+            pub fn press(self, key: bevy::prelude::KeyCode) -> Self {
+                self.event(InputEvent::KeyPress(key))
+            }
+
+            /// Simulate releasing a key
+            pub fn release(self, key: bevy::prelude::KeyCode) -> Self {
+                self.event(InputEvent::KeyRelease(key))
+            }
+
+            /// Press a key in one frame and release it in the next
+            pub fn tap(self, key: bevy::prelude::KeyCode) -> Self {
+                self.press(key).release(key)
+            }
+
+            /// Press a key and release it exactly `duration` seconds of virtual time later
+            pub fn hold(self, key: bevy::prelude::KeyCode, duration: f32) -> Self {
+                self.press(key).wait(duration).release(key)
+            }
+
+            /// Press all `keys` in the same frame and release them together in the next
+            pub fn chord(self, keys: impl IntoIterator<Item = bevy::prelude::KeyCode>) -> Self {
+                let keys: Vec<_> = keys.into_iter().collect();
+                let pressed = keys.iter().fold(self, |input, &key| input.press(key));
+                keys.iter().fold(pressed, |input, &key| input.release(key))
+            }
+
+            /// Simulate raw mouse movement, as used for camera controls
+            pub fn mouse_motion(self, delta: bevy::math::Vec2) -> Self {
+                self.event(InputEvent::MouseMotion(delta))
+            }
+
+            /// Simulate scrolling the mouse wheel by `delta` lines
+            pub fn scroll(self, delta: bevy::math::Vec2) -> Self {
+                self.event(InputEvent::MouseWheel(bevy::input::mouse::MouseScrollUnit::Line, delta))
+            }
+
+            /// Simulate scrolling by `delta` pixels, as touchpads do
+            pub fn scroll_pixels(self, delta: bevy::math::Vec2) -> Self {
+                self.event(InputEvent::MouseWheel(bevy::input::mouse::MouseScrollUnit::Pixel, delta))
+            }
+
+            /// Resize the window to a logical size of `width` x `height`
+            pub fn resize_window(self, width: f32, height: f32) -> Self {
+                self.event(InputEvent::WindowResize(bevy::math::Vec2::new(width, height)))
+            }
+
+            /// Give the window focus or take it away; losing focus releases all keys
+            pub fn focus_window(self, focused: bool) -> Self {
+                self.event(InputEvent::WindowFocus(focused))
+            }
+
+            /// Change the window's scale factor, keeping its physical size
+            pub fn scale_factor(self, scale_factor: f32) -> Self {
+                self.event(InputEvent::WindowScaleFactor(scale_factor))
+            }
+
+            /// Entity of the app's primary window, which `MockInput` sends window input to
+            ///
+            /// # Panics
+            ///
+            /// Panics if the app has no primary window and no `MockInput` created one yet.
+            pub fn window_entity(app: &mut bevy::app::App) -> bevy::ecs::entity::Entity {
+                MockWindow::find(app.world_mut()).expect("The app has no primary window")
+            }
+
+            /// Simulate moving the cursor to a logical `position` in the window
+            pub fn mouse_move(self, position: bevy::math::Vec2) -> Self {
+                self.event(InputEvent::MouseMove(position))
+            }
+
+            /// Simulate clicking the mouse: pressed in one frame, released in the next
+            pub fn click(self, button: bevy::input::mouse::MouseButton) -> Self {
+                self.event(InputEvent::MousePress(button))
+                    .event(InputEvent::MouseRelease(button))
+            }
+
+            /// Connect virtual gamepad number `gamepad`, spawning its entity on first use
+            pub fn connect_gamepad(self, gamepad: usize) -> Self {
+                self.event(InputEvent::GamepadConnect(gamepad))
+            }
+
+            /// Disconnect a virtual gamepad; like a real one, its entity is kept for reconnecting
+            pub fn disconnect_gamepad(self, gamepad: usize) -> Self {
+                self.event(InputEvent::GamepadDisconnect(gamepad))
+            }
+
+            /// Press a gamepad button all the way
+            pub fn press_gamepad(self, gamepad: usize, button: bevy::input::gamepad::GamepadButton) -> Self {
+                self.gamepad_button(gamepad, button, 1.0)
+            }
+
+            /// Release a gamepad button
+            pub fn release_gamepad(self, gamepad: usize, button: bevy::input::gamepad::GamepadButton) -> Self {
+                self.gamepad_button(gamepad, button, 0.0)
+            }
+
+            /// Set the raw analog value of a gamepad button, e.g. a trigger.
+            ///
+            /// The value goes through the gamepad's `GamepadSettings` like real input, so
+            /// values inside the deadzone or below the press threshold are filtered out.
+            pub fn gamepad_button(
+                self,
+                gamepad: usize,
+                button: bevy::input::gamepad::GamepadButton,
+                value: f32,
+            ) -> Self {
+                self.event(InputEvent::GamepadButton(gamepad, button, value))
+            }
+
+            /// Set the raw value of a gamepad axis, e.g. a stick, filtered by its `GamepadSettings`
+            pub fn gamepad_axis(self, gamepad: usize, axis: bevy::input::gamepad::GamepadAxis, value: f32) -> Self {
+                self.event(InputEvent::GamepadAxis(gamepad, axis, value))
+            }
+
+            /// Entity of virtual gamepad number `gamepad`
+            ///
+            /// # Panics
+            ///
+            /// Panics if that gamepad was never connected to `app`.
+            pub fn gamepad_entity(app: &bevy::app::App, gamepad: usize) -> bevy::ecs::entity::Entity {
+                MockGamepads::connected(app.world(), gamepad)
+            }
+
+            /// Send the following events in the `frame`th update after the input is applied,
+            /// counting from 0
+            pub fn at_frame(mut self, frame: u32) -> Self {
+                self.steps.push(InputStep::AtFrame(frame));
                 self
             }
 
-            /// Simulate clicking the mouse
-            pub fn click(mut self, button: bevy::input::mouse::MouseButton) -> Self {
-                self.events.push(InputEvent::MouseClick(button));
+            /// Send the following events in the first update that starts `seconds` of virtual
+            /// time after the input is applied
+            pub fn at_time(mut self, seconds: f32) -> Self {
+                self.steps.push(InputStep::AtTime(duration_from_secs(seconds)));
                 self
             }
 
-            /// Wait for a duration (in seconds)
+            /// Send the following events `duration` seconds of virtual time after the frame
+            /// of the previous events
             pub fn wait(mut self, duration: f32) -> Self {
-                self.events.push(InputEvent::Wait(duration));
+                self.steps.push(InputStep::Wait(duration_from_secs(duration)));
                 self
             }
 
-            /// Apply the input sequence to a Bevy App
+            /// Apply the input sequence to a Bevy App, adding the `InputPlugin` if it is missing.
+            ///
+            /// Consecutive events are sent together and processed by a single update, so
+            /// `press(A).press(B)` is a chord. An event for a key or button that already
+            /// changed in the pending frame starts a new frame instead, so a press is always
+            /// seen as `just_pressed` before its release is seen as `just_released`.
+            ///
+            /// Events scheduled for a frame or time that has already passed are sent in the
+            /// next update.
             pub fn apply_to(self, app: &mut bevy::app::App) {
-                for event in self.events {
+                if !app.is_plugin_added::<bevy::input::InputPlugin>() {
+                    app.add_plugins(bevy::input::InputPlugin);
+                }
+                // What `WindowPlugin` would register
+                app.add_event::<bevy::window::CursorMoved>()
+                    .add_event::<bevy::window::WindowResized>()
+                    .add_event::<bevy::window::WindowFocused>()
+                    .add_event::<bevy::window::WindowScaleFactorChanged>()
+                    .add_event::<bevy::window::WindowBackendScaleFactorChanged>();
+
+                let mut timeline = InputTimeline::new(app, self.window_size);
+                for step in self.steps {
+                    match step {
+                        InputStep::Event(event) => {
+                            if timeline.pending.iter().any(|pending| event.same_button(pending)) {
+                                timeline.flush();
+                            }
+                            timeline.pending.push(event);
+                        }
+                        InputStep::AtFrame(frame) => {
+                            timeline.flush();
+                            timeline.position = InputPosition::Frame(frame);
+                        }
+                        InputStep::AtTime(time) => {
+                            timeline.flush();
+                            let start = timeline.start.expect("`at_time` requires the TimePlugin");
+                            timeline.position = InputPosition::Time(start + time);
+                        }
+                        InputStep::Wait(duration) => {
+                            timeline.flush();
+                            timeline.reach();
+                            let moment = timeline.moment.expect("`wait` and `hold` require the TimePlugin");
+                            timeline.position = InputPosition::Time(moment + duration);
+                        }
+                    }
+                }
+                timeline.flush();
+            }
+        }
+
+        /// Entities of the virtual gamepads connected by `MockInput`, by gamepad number
+        #[derive(bevy::prelude::Resource, Default)]
+        struct MockGamepads(std::collections::HashMap<usize, bevy::ecs::entity::Entity>);
+
+        impl MockGamepads {
+            /// Entity of a gamepad that was connected before
+            fn connected(world: &bevy::ecs::world::World, gamepad: usize) -> bevy::ecs::entity::Entity {
+                world
+                    .get_resource::<MockGamepads>()
+                    .and_then(|gamepads| gamepads.0.get(&gamepad).copied())
+                    .unwrap_or_else(|| panic!("Gamepad {} was never connected; use `connect_gamepad` first", gamepad))
+            }
+        }
+
+        /// The window `MockInput` sends window and cursor input to
+        struct MockWindow;
+
+        impl MockWindow {
+            fn find(world: &mut bevy::ecs::world::World) -> Option<bevy::ecs::entity::Entity> {
+                world
+                    .query_filtered::<bevy::ecs::entity::Entity, bevy::prelude::With<bevy::window::PrimaryWindow>>()
+                    .iter(world)
+                    .next()
+            }
+
+            /// The primary window, or a new focused window of `size` that no OS window backs
+            fn get_or_spawn(world: &mut bevy::ecs::world::World, size: bevy::math::Vec2) -> bevy::ecs::entity::Entity {
+                if let Some(window) = Self::find(world) {
+                    return window;
+                }
+                let window = bevy::window::Window {
+                    resolution: bevy::window::WindowResolution::new(size.x, size.y),
+                    focused: true,
+                    ..Default::default()
+                };
+                world.spawn((window, bevy::window::PrimaryWindow)).id()
+            }
+
+            /// Set the logical size of `window` like the windowing backend does
+            fn resize(world: &mut bevy::ecs::world::World, window: bevy::ecs::entity::Entity, size: bevy::math::Vec2) {
+                let mut window_state = world.get_mut::<bevy::window::Window>(window).unwrap();
+                window_state.resolution.set(size.x, size.y);
+                world.send_event(bevy::window::WindowResized {
+                    window,
+                    width: size.x,
+                    height: size.y,
+                });
+            }
+        }
+
+        /// Where the next events of a `MockInput` timeline are sent
+        enum InputPosition {
+            /// In the next update
+            Next,
+            Frame(u32),
+            /// Once `Time<Virtual>` has reached this elapsed time
+            Time(std::time::Duration),
+        }
+
+        /// Progress of a `MockInput` being applied to an app
+        struct InputTimeline<'a> {
+            app: &'a mut bevy::app::App,
+            pending: Vec<InputEvent>,
+            position: InputPosition,
+            /// Updates run so far
+            frames: u32,
+            /// Virtual time when the input was applied
+            start: Option<std::time::Duration>,
+            /// Virtual time at the start of the frame of the latest events
+            moment: Option<std::time::Duration>,
+            window_size: bevy::math::Vec2,
+        }
+
+        impl<'a> InputTimeline<'a> {
+            fn new(app: &'a mut bevy::app::App, window_size: bevy::math::Vec2) -> Self {
+                let start = Self::virtual_elapsed(app);
+                InputTimeline {
+                    app,
+                    pending: Vec::new(),
+                    position: InputPosition::Next,
+                    frames: 0,
+                    start,
+                    moment: start,
+                    window_size,
+                }
+            }
+
+            fn virtual_elapsed(app: &bevy::app::App) -> Option<std::time::Duration> {
+                app.world()
+                    .get_resource::<bevy::time::Time<bevy::time::Virtual>>()
+                    .map(|time| time.elapsed())
+            }
+
+            /// Update the app until the next update is the one at `position`
+            fn reach(&mut self) {
+                match std::mem::replace(&mut self.position, InputPosition::Next) {
+                    InputPosition::Next => return,
+                    InputPosition::Frame(frame) => {
+                        while self.frames < frame {
+                            self.app.update();
+                            self.frames += 1;
+                        }
+                    }
+                    InputPosition::Time(time) => {
+                        let elapsed = Self::virtual_elapsed(self.app).unwrap();
+                        if time > elapsed {
+                            self.frames += advance_virtual_time(self.app, time - elapsed) as u32;
+                        }
+                    }
+                }
+                self.moment = Self::virtual_elapsed(self.app);
+            }
+
+            /// Send the pending events and run the update that processes them
+            fn flush(&mut self) {
+                if self.pending.is_empty() {
+                    return;
+                }
+                self.reach();
+                self.moment = Self::virtual_elapsed(self.app);
+
+                let window_size = self.window_size;
+                let world = self.app.world_mut();
+                for event in self.pending.drain(..) {
                     match event {
-                        InputEvent::KeyPress(key) => {
-                            // Send key press event
-                            app.world_mut().send_event(bevy::input::keyboard::KeyboardInput {
+                        InputEvent::KeyPress(key) | InputEvent::KeyRelease(key) => {
+                            let state = match event {
+                                InputEvent::KeyPress(_) => bevy::input::ButtonState::Pressed,
+                                _ => bevy::input::ButtonState::Released,
+                            };
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            world.send_event(bevy::input::keyboard::KeyboardInput {
                                 logical_key: bevy::input::keyboard::Key::Character(format!("{:?}", key).into()),
                                 key_code: key,
-                                state: bevy::input::ButtonState::Pressed,
-                                window: bevy::prelude::Entity::PLACEHOLDER,
+                                state,
+                                text: None,
+                                repeat: false,
+                                window,
                             });
                         }
-                        InputEvent::KeyRelease(key) => {
-                            // Send key release event
-                            app.world_mut().send_event(bevy::input::keyboard::KeyboardInput {
-                                logical_key: bevy::input::keyboard::Key::Character(format!("{:?}", key).into()),
-                                key_code: key,
-                                state: bevy::input::ButtonState::Released,
-                                window: bevy::prelude::Entity::PLACEHOLDER,
+                        InputEvent::MouseMove(position) => {
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            let mut window_state = world.get_mut::<bevy::window::Window>(window).unwrap();
+                            let delta = window_state.cursor_position().map(|previous| position - previous);
+                            window_state.set_cursor_position(Some(position));
+                            world.send_event(bevy::window::CursorMoved { window, position, delta });
+                        }
+                        InputEvent::MouseMotion(delta) => {
+                            world.send_event(bevy::input::mouse::MouseMotion { delta });
+                        }
+                        InputEvent::MouseWheel(unit, delta) => {
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            world.send_event(bevy::input::mouse::MouseWheel {
+                                unit,
+                                x: delta.x,
+                                y: delta.y,
+                                window,
                             });
                         }
-                        InputEvent::MouseMove(pos) => {
-                            // Update cursor position
-                            app.world_mut().send_event(bevy::window::CursorMoved {
-                                window: bevy::prelude::Entity::PLACEHOLDER,
-                                position: pos,
-                                delta: None,
+                        InputEvent::WindowResize(size) => {
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            MockWindow::resize(world, window, size);
+                        }
+                        InputEvent::WindowFocus(focused) => {
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            world.get_mut::<bevy::window::Window>(window).unwrap().focused = focused;
+                            world.send_event(bevy::window::WindowFocused { window, focused });
+                            if !focused {
+                                world.send_event(bevy::input::keyboard::KeyboardFocusLost);
+                            }
+                        }
+                        InputEvent::WindowScaleFactor(scale_factor) => {
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            let mut window_state = world.get_mut::<bevy::window::Window>(window).unwrap();
+                            let physical = window_state.resolution.physical_size().as_vec2();
+                            window_state.resolution.set_scale_factor(scale_factor);
+                            world.send_event(bevy::window::WindowBackendScaleFactorChanged {
+                                window,
+                                scale_factor: scale_factor as f64,
+                            });
+                            world.send_event(bevy::window::WindowScaleFactorChanged {
+                                window,
+                                scale_factor: scale_factor as f64,
                             });
+                            // The physical size stays, so the logical size changes
+                            MockWindow::resize(world, window, physical / scale_factor);
                         }
-                        InputEvent::MouseClick(button) => {
-                            // Send mouse click event
-                            app.world_mut().send_event(bevy::input::mouse::MouseButtonInput {
+                        InputEvent::MousePress(button) | InputEvent::MouseRelease(button) => {
+                            let state = match event {
+                                InputEvent::MousePress(_) => bevy::input::ButtonState::Pressed,
+                                _ => bevy::input::ButtonState::Released,
+                            };
+                            let window = MockWindow::get_or_spawn(world, window_size);
+                            world.send_event(bevy::input::mouse::MouseButtonInput { button, state, window });
+                        }
+                        // Sent like the gilrs backend does: raw events for `InputPlugin` to
+                        // filter, plus the connection event that adds the `Gamepad` component
+                        InputEvent::GamepadConnect(gamepad) | InputEvent::GamepadDisconnect(gamepad) => {
+                            use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent, RawGamepadEvent};
+
+                            world.init_resource::<MockGamepads>();
+                            let entity = world.resource::<MockGamepads>().0.get(&gamepad).copied();
+                            let entity = match (entity, &event) {
+                                (Some(entity), _) => entity,
+                                (None, InputEvent::GamepadConnect(_)) => {
+                                    let entity = world.spawn_empty().id();
+                                    world.resource_mut::<MockGamepads>().0.insert(gamepad, entity);
+                                    entity
+                                }
+                                (None, _) => panic!("Gamepad {} was never connected; use `connect_gamepad` first", gamepad),
+                            };
+                            let connection = match event {
+                                InputEvent::GamepadConnect(_) => GamepadConnection::Connected {
+                                    name: format!("Mock Gamepad {}", gamepad),
+                                    vendor_id: None,
+                                    product_id: None,
+                                },
+                                _ => GamepadConnection::Disconnected,
+                            };
+                            let connection = GamepadConnectionEvent::new(entity, connection);
+                            world.send_event(RawGamepadEvent::Connection(connection.clone()));
+                            world.send_event(connection);
+                        }
+                        InputEvent::GamepadButton(gamepad, button, value) => {
+                            let event = bevy::input::gamepad::RawGamepadButtonChangedEvent::new(
+                                MockGamepads::connected(world, gamepad),
                                 button,
-                                state: bevy::input::ButtonState::Pressed,
-                                window: bevy::prelude::Entity::PLACEHOLDER,
-                            });
+                                value,
+                            );
+                            world.send_event(bevy::input::gamepad::RawGamepadEvent::Button(event));
+                            world.send_event(event);
                         }
-                        InputEvent::Wait(duration) => {
-                            // Advance time
-                            let frames = (duration * 60.0) as usize;
-                            for _ in 0..frames {
-                                app.update();
-                            }
+                        InputEvent::GamepadAxis(gamepad, axis, value) => {
+                            let event = bevy::input::gamepad::RawGamepadAxisChangedEvent::new(
+                                MockGamepads::connected(world, gamepad),
+                                axis,
+                                value,
+                            );
+                            world.send_event(bevy::input::gamepad::RawGamepadEvent::Axis(event));
+                            world.send_event(event);
                         }
                     }
-                    // Update after each event
-                    app.update();
                 }
+                self.app.update();
+                self.frames += 1;
             }
         }
     }
@@ -180,6 +611,12 @@ pub fn generate_fixtures() -> TokenStream {
             fn apply_to(self, app: &mut bevy::app::App);
         }
 
+        /// Trait for values built by #[bevy_test] for its `#[fixture]` parameters
+        pub trait FromTestApp: Sized {
+            /// Build the value, spawning or inserting whatever it needs into the app
+            fn from_test_app(app: &mut bevy::app::App) -> Self;
+        }
+
         /// Macro for defining fixtures
         #[macro_export]
         macro_rules! fixture {
@@ -0,0 +1,125 @@
+//! World dumps for failing tests
+//!
+//! A generated test that panics prints every entity of its world, with its `Name`,
+//! archetype and reflected component values, before the panic carries on.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse::Parse, parse::ParseStream, Result};
+
+/// Bytes of dump printed when a test does not configure `world_dump`
+pub const DEFAULT_DUMP_LIMIT: usize = 8192;
+
+/// Size limit set by `world_dump = N`, or `None` for `world_dump = off`
+#[derive(Clone, Copy)]
+pub struct DumpLimit(pub Option<usize>);
+
+impl Default for DumpLimit {
+    fn default() -> Self {
+        DumpLimit(Some(DEFAULT_DUMP_LIMIT))
+    }
+}
+
+impl Parse for DumpLimit {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::Ident) {
+            let ident: syn::Ident = input.parse()?;
+            return if ident == "off" {
+                Ok(DumpLimit(None))
+            } else {
+                Err(syn::Error::new(
+                    ident.span(),
+                    "Expected a size in bytes or `off`",
+                ))
+            };
+        }
+        let lit: syn::LitInt = input.parse()?;
+        Ok(DumpLimit(Some(lit.base10_parse()?)))
+    }
+}
+
+/// Generate `dump_world`, shared by `bevy_test_utils!()` and the inline scenario tests,
+/// along with the `short_type_name` helper that `WorldDiff` also uses
+pub fn generate_dump_world() -> TokenStream {
+    quote! {
+        /// `a::b::Foo<c::Bar>` -> `Foo<Bar>`
+        #[doc(hidden)]
+        pub fn short_type_name(name: &str) -> String {
+            let mut short = String::with_capacity(name.len());
+            let mut segment_start = 0;
+            let mut chars = name.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c == ':' && chars.peek() == Some(&':') {
+                    chars.next();
+                    short.truncate(segment_start);
+                } else {
+                    short.push(c);
+                    if !(c.is_alphanumeric() || c == '_') {
+                        segment_start = short.len();
+                    }
+                }
+            }
+            short
+        }
+
+        /// Describe every entity of `world`: its `Name`, archetype and reflected component
+        /// values, cut off after about `limit` bytes
+        pub fn dump_world(world: &bevy::ecs::world::World, limit: usize) -> String {
+            use std::fmt::Write;
+
+            let registry = world
+                .get_resource::<bevy::ecs::reflect::AppTypeRegistry>()
+                .map(|registry| registry.read());
+            let entities: Vec<_> = world.iter_entities().collect();
+
+            let mut dump = format!("World dump ({} entities):\n", entities.len());
+            for (index, entity) in entities.iter().enumerate() {
+                let components: Vec<_> = world
+                    .inspect_entity(entity.id())
+                    .map(|components| components.collect())
+                    .unwrap_or_default();
+                let names: Vec<_> = components.iter().map(|info| short_type_name(info.name())).collect();
+                let name = entity
+                    .get::<bevy::ecs::name::Name>()
+                    .map(|name| format!(" {:?}", name.as_str()))
+                    .unwrap_or_default();
+
+                let mut entry = String::new();
+                let _ = writeln!(
+                    entry,
+                    "  {}{} [archetype {}: {}]",
+                    entity.id(),
+                    name,
+                    entity.archetype().id().index(),
+                    names.join(", ")
+                );
+                if let Some(registry) = &registry {
+                    for (info, short) in components.iter().zip(&names) {
+                        let Some(type_id) = info.type_id() else { continue };
+                        if type_id == std::any::TypeId::of::<bevy::ecs::name::Name>() {
+                            continue;
+                        }
+                        let value = registry
+                            .get_type_data::<bevy::ecs::reflect::ReflectComponent>(type_id)
+                            .and_then(|reflect| reflect.reflect(entity));
+                        if let Some(value) = value {
+                            let _ = writeln!(entry, "    {}: {:?}", short, value);
+                        }
+                    }
+                }
+
+                if dump.len() + entry.len() > limit {
+                    let _ = writeln!(
+                        dump,
+                        "  ... {} more entities not shown (limit of {} bytes)",
+                        entities.len() - index,
+                        limit
+                    );
+                    break;
+                }
+                dump.push_str(&entry);
+            }
+            dump
+        }
+    }
+}
@@ -39,11 +39,20 @@ mod assertions;
 mod attribute;
 mod builders;
 mod component;
+mod dump;
+mod logs;
+mod repeat;
 mod scenario;
+mod schedule;
+mod snapshot;
 mod system;
+mod time;
 
 /// Creates a complete test scenario with given/when/then structure.
 ///
+/// Settings go in parentheses after the name, e.g.
+/// `test_scenario!(name(ambiguity = deny, executors = both) { ... })`.
+///
 /// # Example
 /// ```
 /// test_scenario!(law_enactment {
@@ -78,8 +87,10 @@ pub fn test_scenario(input: TokenStream) -> TokenStream {
 ///
 /// // Now you can use:
 /// // - TestApp trait for #[bevy_test]
+/// // - with_test_app() to reach the app of a #[bevy_test] without parameters
 /// // - MockWorld for world building
 /// // - MockInput for input simulation
+/// // - WorldSnapshot and WorldDiff to compare the world before and after a step
 /// // - Rich assertion macros
 /// ```
 #[proc_macro]
@@ -97,6 +108,11 @@ pub fn bevy_test_utils(_input: TokenStream) -> TokenStream {
 
     // Add test helpers for #[bevy_test]
     output.extend(attribute::generate_test_helpers());
+    output.extend(attribute::generate_watchdog());
+    output.extend(logs::generate_log_capture());
+    output.extend(repeat::generate_repeat_runtime());
+    output.extend(dump::generate_dump_world());
+    output.extend(snapshot::generate_world_snapshot());
 
     output.into()
 }
@@ -198,10 +214,60 @@ pub fn property_test(input: TokenStream) -> TokenStream {
 /// // Or without app parameter
 /// #[bevy_test(headless)]
 /// fn test_simple() {
-///     // App is created automatically
+///     // App is created automatically and reachable from any helper
+///     with_test_app(|app| app.update());
 ///     assert_eq!(2 + 2, 4);
 /// }
 /// ```
+///
+/// Parameters are injected by type: `&mut App` or `&mut TestApp` receive the test app,
+/// `&mut World` its world, and `#[fixture]` parameters are built through `FromTestApp`
+/// before the test runs. Any other parameter must be bound from `cases(...)`.
+///
+/// Attributes such as `#[should_panic]`, `#[ignore]` and doc comments are carried over
+/// to the generated test, as are its visibility and return type, so functions returning
+/// `Result<(), E>` can use `?` just like with `#[test]`.
+///
+/// # Configuration
+///
+/// - `profile = ecs_only | minimal | headless_full` - Plugin set the app starts with:
+///   a bare `App`, `MinimalPlugins` (the default), or `MinimalPlugins` plus state, input,
+///   in-memory assets (`TestAssets`), transforms and a virtual primary window
+/// - `headless` - Shorthand for `profile = headless_full`
+/// - `plugins = [...]` - Extra plugins added after the profile
+/// - `fixed_dt = 1/30` - Seconds every update advances `Time`, `Time<Virtual>` and
+///   `Time<Fixed>` by (default `1/60`)
+/// - `states = [GameState::Playing, ...]` - Initial states, entered (running their
+///   `OnEnter` schedules) before the test body; see `TestApp::set_state`
+/// - `ambiguity = deny` - Fail the test when a schedule has systems with conflicting data
+///   access and no ordering between them, listing each pair and what they conflict on
+/// - `setup = path` / `teardown = path` - Functions taking `&mut App`, called after the app
+///   is configured and after the test body (even when it panicked)
+/// - `capture_logs` - Buffer the app's logs instead of printing them, printing them only
+///   if the test fails; assert on them with `assert_logged!` and `assert_no_errors_logged!`
+/// - `executors = both` - Expand into `<fn>_single_threaded` and `<fn>_multi_threaded`,
+///   running the app's schedules on that executor; `single_threaded` or `multi_threaded`
+///   pins the test to one
+/// - `repeat = N` - Run the test on `N` fresh apps, each with its own seed ordering the
+///   systems that access the same data without an explicit order; a failure names the
+///   iteration and the seed, and `BEVY_TEST_SEED=<seed>` reruns just that one
+/// - `rng = |seed| ...` - With `repeat`, build a resource from each iteration's seed
+/// - `world_dump = N | off` - When the test panics, print every entity with its `Name`,
+///   archetype and reflected components, up to `N` bytes (default 8192), before the panic
+///   carries on
+/// - `timeout = N` - Fail the test if it runs longer than `N` milliseconds, reporting
+///   the frame and the schedule that was running when the watchdog fired; systems run on
+///   the single-threaded executor so a hung one stays on the abandoned test thread
+/// - `cases(name = value, ...)` / `case = value` - Expand into one test per case
+///   (`<fn>_<name>`, or `<fn>_case_<n>` for unnamed cases), binding the value to the
+///   function's extra parameters; several parameters take a tuple
+///
+/// ```
+/// #[bevy_test(cases(weak = (10, 1), strong = (100, 5)))]
+/// fn test_damage(app: &mut TestApp, damage: i32, enemies: usize) {
+///     // Expands to `test_damage_weak` and `test_damage_strong`
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn bevy_test(args: TokenStream, input: TokenStream) -> TokenStream {
     // Parse the function from input
@@ -216,3 +282,33 @@ pub fn bevy_test(args: TokenStream, input: TokenStream) -> TokenStream {
 
     attribute::expand_bevy_test(config, function).into()
 }
+
+/// Shares #[bevy_test] configuration between all tests of an inline module.
+///
+/// Takes the same options as `#[bevy_test]`, applied as defaults to every `#[bevy_test]`
+/// function in the module and its submodules. A test's own options override them; list
+/// options such as `plugins = [...]` replace the module's list. Nested modules may add
+/// their own `#[bevy_test_module]` on top.
+///
+/// # Example
+/// ```
+/// #[bevy_test_module(plugins = [CombatPlugin], timeout = 1000, setup = spawn_arena, teardown = check_leaks)]
+/// mod combat_tests {
+///     use super::*;
+///
+///     #[bevy_test]
+///     fn test_attack(app: &mut TestApp) {
+///         // `spawn_arena(app)` already ran
+///     }
+///
+///     #[bevy_test(timeout = 5000)]
+///     fn test_long_fight(app: &mut TestApp) {
+///         // Same plugins and hooks, longer timeout
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn bevy_test_module(args: TokenStream, input: TokenStream) -> TokenStream {
+    let module = parse_macro_input!(input as syn::ItemMod);
+    attribute::expand_bevy_test_module(args.into(), module).into()
+}
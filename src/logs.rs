@@ -0,0 +1,136 @@
+//! Log capture for tests
+//!
+//! Buffers the `tracing` output of a test app instead of writing it to stdout,
+//! so tests can assert on warnings and errors and a failing test shows its logs.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generate the runtime side of `TestApp::capture_logs`
+pub fn generate_log_capture() -> TokenStream {
+    quote! {
+        /// A log event recorded while `TestApp::capture_logs` is active
+        #[derive(Debug, Clone)]
+        pub struct CapturedLog {
+            pub level: bevy::log::Level,
+            pub target: String,
+            /// The formatted message, followed by any other fields as `name=value`
+            pub message: String,
+        }
+
+        impl std::fmt::Display for CapturedLog {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{:>5} {}: {}", self.level, self.target, self.message)
+            }
+        }
+
+        /// Logs buffered by the subscriber `capture_logs` installed on the test thread.
+        ///
+        /// Prints them if the app is dropped while the test panics, or once a test that
+        /// returns an error has finished.
+        #[doc(hidden)]
+        #[derive(bevy::prelude::Resource)]
+        pub struct LogCapture {
+            logs: std::sync::Arc<std::sync::Mutex<Vec<CapturedLog>>>,
+        }
+
+        /// Keeps the subscriber installed for the lifetime of the app
+        struct LogCaptureGuard {
+            _guard: bevy::log::tracing::subscriber::DefaultGuard,
+        }
+
+        impl LogCapture {
+            fn install(app: &mut bevy::app::App) {
+                use bevy::ecs::schedule::IntoScheduleConfigs;
+                use bevy::log::tracing_subscriber::layer::SubscriberExt;
+
+                if app.world().contains_resource::<LogCapture>() {
+                    return;
+                }
+
+                let logs = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+                let subscriber = bevy::log::tracing_subscriber::registry()
+                    .with(LogCaptureLayer(logs.clone()));
+                let _guard = bevy::log::tracing::subscriber::set_default(subscriber);
+                app.insert_non_send_resource(LogCaptureGuard { _guard });
+                app.insert_resource(LogCapture { logs });
+
+                // The subscriber only sees this thread, so keep every system on it
+                LogCapture::run_single_threaded(app.world_mut());
+                app.add_systems(bevy::app::PreStartup, LogCapture::run_single_threaded.ambiguous_with_all());
+                app.add_systems(bevy::app::First, LogCapture::run_single_threaded.ambiguous_with_all());
+            }
+
+            fn run_single_threaded(world: &mut bevy::ecs::world::World) {
+                let mut schedules = world.resource_mut::<bevy::ecs::schedule::Schedules>();
+                for (_, schedule) in schedules.iter_mut() {
+                    if schedule.get_executor_kind() != bevy::ecs::schedule::ExecutorKind::SingleThreaded {
+                        schedule.set_executor_kind(bevy::ecs::schedule::ExecutorKind::SingleThreaded);
+                    }
+                }
+            }
+
+            fn logs(&self) -> Vec<CapturedLog> {
+                self.logs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+            }
+
+            fn print(&self) {
+                let logs = self.logs();
+                if !logs.is_empty() {
+                    eprintln!("---- captured logs ({}) ----", logs.len());
+                    for log in &logs {
+                        eprintln!("{}", log);
+                    }
+                }
+            }
+        }
+
+        impl Drop for LogCapture {
+            fn drop(&mut self) {
+                if std::thread::panicking() {
+                    self.print();
+                }
+            }
+        }
+
+        struct LogCaptureLayer(std::sync::Arc<std::sync::Mutex<Vec<CapturedLog>>>);
+
+        impl<S: bevy::log::tracing::Subscriber> bevy::log::tracing_subscriber::Layer<S> for LogCaptureLayer {
+            fn on_event(
+                &self,
+                event: &bevy::log::tracing::Event<'_>,
+                _ctx: bevy::log::tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                let mut visitor = LogMessageVisitor(String::new(), String::new());
+                event.record(&mut visitor);
+                let LogMessageVisitor(mut message, fields) = visitor;
+                message.push_str(&fields);
+
+                let metadata = event.metadata();
+                self.0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(CapturedLog {
+                        level: *metadata.level(),
+                        target: metadata.target().to_string(),
+                        message,
+                    });
+            }
+        }
+
+        /// Collects the `message` field and the remaining fields separately
+        struct LogMessageVisitor(String, String);
+
+        impl bevy::log::tracing::field::Visit for LogMessageVisitor {
+            fn record_debug(&mut self, field: &bevy::log::tracing::field::Field, value: &dyn std::fmt::Debug) {
+                use std::fmt::Write;
+
+                if field.name() == "message" {
+                    let _ = write!(self.0, "{:?}", value);
+                } else {
+                    let _ = write!(self.1, " {}={:?}", field.name(), value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Repeated test runs with a varied system order
+//!
+//! `#[bevy_test(repeat = N)]` runs a test on `N` fresh apps. Each run gets its own seed,
+//! which picks the order of systems that access the same data without being ordered,
+//! and seeds the RNG resource configured with `rng = ...`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Seed the system order and RNG of an app in setup; `__bevy_test_seed` must be in scope
+pub fn generate_seeded_setup(rng: Option<&syn::Expr>) -> TokenStream {
+    let shuffle = crate::schedule::configure_all_schedules(syn::parse_quote! {
+        fn shuffle_system_order(world: &mut bevy::ecs::world::World) {
+            let shuffle = *world.resource::<ShuffleSystemOrder>();
+            let mut schedules = world.resource_mut::<bevy::ecs::schedule::Schedules>();
+            for (_, schedule) in schedules.iter_mut() {
+                schedule.add_build_pass(shuffle);
+            }
+        }
+    });
+    let rng = rng.map(|rng| {
+        quote! {
+            app.insert_resource((#rng)(__bevy_test_seed));
+        }
+    });
+
+    quote! {
+        app.insert_resource(ShuffleSystemOrder::new(__bevy_test_seed));
+        #shuffle
+        #rng
+    }
+}
+
+/// Run `test` once per seed, naming the iteration and seed of a failure.
+///
+/// With `returns`, `test` evaluates to the function's `Termination` value and the loop
+/// evaluates to an `ExitCode`.
+pub fn generate_repeat(
+    test_name: &syn::Ident,
+    repeat: u32,
+    returns: bool,
+    test: TokenStream,
+) -> TokenStream {
+    let (passed, finish) = if returns {
+        (
+            quote! {
+                Ok(value) => {
+                    let code = std::process::Termination::report(value);
+                    if code != std::process::ExitCode::SUCCESS {
+                        eprintln!("{}", TestRepeat::describe(iteration, seeds.len(), __bevy_test_seed));
+                        return code;
+                    }
+                }
+            },
+            quote! { std::process::ExitCode::SUCCESS },
+        )
+    } else {
+        (quote! { Ok(()) => {} }, quote! {})
+    };
+
+    quote! {
+        let seeds = TestRepeat::seeds(stringify!(#test_name), #repeat);
+        for (iteration, &__bevy_test_seed) in seeds.iter().enumerate() {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| { #test }));
+            match result {
+                #passed
+                Err(panic) => TestRepeat::fail(panic, iteration, seeds.len(), __bevy_test_seed),
+            }
+        }
+        #finish
+    }
+}
+
+/// Generate the runtime side of `repeat`
+pub fn generate_repeat_runtime() -> TokenStream {
+    quote! {
+        /// Seeds of a `#[bevy_test(repeat = N)]` run
+        #[doc(hidden)]
+        pub struct TestRepeat;
+
+        impl TestRepeat {
+            /// `repeat` seeds derived from the test name, or only the seed in `BEVY_TEST_SEED`
+            pub fn seeds(test: &str, repeat: u32) -> Vec<u64> {
+                if let Ok(seed) = std::env::var("BEVY_TEST_SEED") {
+                    let seed = seed.trim();
+                    let parsed = match seed.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16),
+                        None => seed.parse(),
+                    };
+                    return vec![parsed.unwrap_or_else(|_| panic!("BEVY_TEST_SEED is not a valid seed: {}", seed))];
+                }
+
+                // FNV-1a, so the seeds are the same on every run and platform
+                let base = test
+                    .bytes()
+                    .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+                (0..repeat as u64)
+                    .map(|iteration| ShuffleSystemOrder::next(&mut (base ^ iteration)))
+                    .collect()
+            }
+
+            pub fn describe(iteration: usize, repeat: usize, seed: u64) -> String {
+                format!(
+                    "iteration {} of {} failed with seed {:#018x}; rerun it alone with BEVY_TEST_SEED={:#x}",
+                    iteration + 1,
+                    repeat,
+                    seed,
+                    seed
+                )
+            }
+
+            /// Panic again with the iteration and seed appended to the original message
+            pub fn fail(panic: Box<dyn std::any::Any + Send>, iteration: usize, repeat: usize, seed: u64) -> ! {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned());
+                match message {
+                    Some(message) => panic!("{}\n{}", message, Self::describe(iteration, repeat, seed)),
+                    None => {
+                        eprintln!("{}", Self::describe(iteration, repeat, seed));
+                        std::panic::resume_unwind(panic)
+                    }
+                }
+            }
+        }
+
+        /// Schedule build pass that orders every pair of conflicting, unordered systems
+        /// along a random topological order picked by `seed`
+        #[doc(hidden)]
+        #[derive(bevy::prelude::Resource, Debug, Clone, Copy)]
+        pub struct ShuffleSystemOrder {
+            seed: u64,
+        }
+
+        impl ShuffleSystemOrder {
+            pub fn new(seed: u64) -> Self {
+                ShuffleSystemOrder { seed }
+            }
+
+            /// splitmix64
+            fn next(state: &mut u64) -> u64 {
+                *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            }
+
+            fn conflict(
+                graph: &bevy::ecs::schedule::ScheduleGraph,
+                a: bevy::ecs::schedule::NodeId,
+                b: bevy::ecs::schedule::NodeId,
+            ) -> bool {
+                if graph.ambiguous_with_all.contains(&a) || graph.ambiguous_with_all.contains(&b) {
+                    return false;
+                }
+                match (graph.get_system_at(a), graph.get_system_at(b)) {
+                    (Some(a), Some(b)) => {
+                        a.is_exclusive()
+                            || b.is_exclusive()
+                            || !a.component_access().is_compatible(b.component_access())
+                    }
+                    _ => false,
+                }
+            }
+        }
+
+        impl bevy::ecs::schedule::ScheduleBuildPass for ShuffleSystemOrder {
+            type EdgeOptions = ();
+
+            fn add_dependency(
+                &mut self,
+                _from: bevy::ecs::schedule::NodeId,
+                _to: bevy::ecs::schedule::NodeId,
+                _options: Option<&Self::EdgeOptions>,
+            ) {
+            }
+
+            fn collapse_set(
+                &mut self,
+                _set: bevy::ecs::schedule::NodeId,
+                _systems: &[bevy::ecs::schedule::NodeId],
+                _dependency_flattened: &bevy::ecs::schedule::graph::DiGraph,
+            ) -> impl Iterator<Item = (bevy::ecs::schedule::NodeId, bevy::ecs::schedule::NodeId)> {
+                std::iter::empty()
+            }
+
+            fn build(
+                &mut self,
+                _world: &mut bevy::ecs::world::World,
+                graph: &mut bevy::ecs::schedule::ScheduleGraph,
+                dependency_flattened: &mut bevy::ecs::schedule::graph::DiGraph,
+            ) -> Result<(), bevy::ecs::schedule::ScheduleBuildError> {
+                use bevy::ecs::schedule::graph::Direction;
+
+                // Kahn's algorithm, picking a random node among the ready ones
+                let mut state = self.seed;
+                let mut incoming: std::collections::HashMap<_, _> = dependency_flattened
+                    .nodes()
+                    .map(|node| (node, dependency_flattened.neighbors_directed(node, Direction::Incoming).count()))
+                    .collect();
+                let mut ready: Vec<_> = dependency_flattened
+                    .nodes()
+                    .filter(|node| incoming[node] == 0)
+                    .collect();
+                let mut order = Vec::with_capacity(incoming.len());
+                while !ready.is_empty() {
+                    let index = (Self::next(&mut state) % ready.len() as u64) as usize;
+                    let node = ready.swap_remove(index);
+                    order.push(node);
+                    for next in dependency_flattened.neighbors_directed(node, Direction::Outgoing) {
+                        let count = incoming.get_mut(&next).unwrap();
+                        *count -= 1;
+                        if *count == 0 {
+                            ready.push(next);
+                        }
+                    }
+                }
+
+                // Leave cyclic graphs alone so the schedule reports the cycle itself
+                if order.len() != incoming.len() {
+                    return Ok(());
+                }
+
+                // Edges along a topological order can never form a cycle
+                for (index, &a) in order.iter().enumerate() {
+                    for &b in &order[index + 1..] {
+                        if Self::conflict(graph, a, b) {
+                            dependency_flattened.add_edge(a, b);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -7,14 +7,21 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{parse::Parse, parse::ParseStream, Expr, Ident, Result, Token};
 
+use crate::dump::DumpLimit;
+use crate::schedule::{AmbiguityPolicy, Executor};
+
 pub struct TestScenario {
     name: Ident,
+    ambiguity: AmbiguityPolicy,
+    executors: Vec<Executor>,
+    world_dump: DumpLimit,
     given: GivenClause,
     when: WhenClause,
     then: ThenClause,
 }
 
 struct GivenClause {
+    states: Vec<Expr>,
     resources: Vec<Expr>,
     events: Vec<Expr>,
     systems: Vec<Expr>,
@@ -56,6 +63,26 @@ impl Parse for TestScenario {
     fn parse(input: ParseStream) -> Result<Self> {
         let name = input.parse()?;
 
+        // Optional settings: `test_scenario!(name(ambiguity = deny, world_dump = off) { ... })`
+        let mut ambiguity = AmbiguityPolicy::default();
+        let mut executors = Vec::new();
+        let mut world_dump = DumpLimit::default();
+        if input.peek(syn::token::Paren) {
+            let options;
+            syn::parenthesized!(options in input);
+            while !options.is_empty() {
+                let option: Ident = options.parse()?;
+                options.parse::<Token![=]>()?;
+                match option.to_string().as_str() {
+                    "ambiguity" => ambiguity = options.parse()?,
+                    "executors" => executors = Executor::parse_list(&options)?,
+                    "world_dump" => world_dump = options.parse()?,
+                    _ => return Err(syn::Error::new(option.span(), "Unknown scenario option")),
+                }
+                options.parse::<Token![,]>().ok();
+            }
+        }
+
         let content;
         syn::braced!(content in input);
 
@@ -93,6 +120,9 @@ impl Parse for TestScenario {
 
         Ok(TestScenario {
             name,
+            ambiguity,
+            executors,
+            world_dump,
             given,
             when,
             then,
@@ -101,6 +131,7 @@ impl Parse for TestScenario {
 }
 
 fn parse_given_clause(input: ParseStream) -> Result<GivenClause> {
+    let mut states = Vec::new();
     let mut resources = Vec::new();
     let mut events = Vec::new();
     let mut systems = Vec::new();
@@ -111,6 +142,17 @@ fn parse_given_clause(input: ParseStream) -> Result<GivenClause> {
         input.parse::<Token![:]>()?;
 
         match field_name.to_string().as_str() {
+            "state" => {
+                states.push(input.parse()?);
+            }
+            "states" => {
+                let content;
+                syn::bracketed!(content in input);
+                while !content.is_empty() {
+                    states.push(content.parse()?);
+                    content.parse::<Token![,]>().ok();
+                }
+            }
             "resources" => {
                 let content;
                 syn::bracketed!(content in input);
@@ -163,6 +205,7 @@ fn parse_given_clause(input: ParseStream) -> Result<GivenClause> {
     }
 
     Ok(GivenClause {
+        states,
         resources,
         events,
         systems,
@@ -298,10 +341,53 @@ fn parse_then_clause(input: ParseStream) -> Result<ThenClause> {
 
 impl TestScenario {
     pub fn expand(&self) -> TokenStream {
-        let test_name = &self.name;
+        match self.executors.as_slice() {
+            [] => self.expand_test(&self.name, None),
+            [executor] => self.expand_test(&self.name, Some(*executor)),
+            // One test per executor
+            executors => executors
+                .iter()
+                .map(|executor| {
+                    let test_name = quote::format_ident!("{}_{}", self.name, executor.suffix());
+                    self.expand_test(&test_name, Some(*executor))
+                })
+                .collect(),
+        }
+    }
+
+    fn expand_test(&self, test_name: &Ident, executor: Option<Executor>) -> TokenStream {
         let setup = self.generate_setup();
         let actions = self.generate_actions();
         let assertions = self.generate_assertions();
+        let time_control = crate::time::generate_time_control(
+            crate::time::step_nanos(crate::time::DEFAULT_FIXED_DT),
+            quote! { false },
+        );
+        let ambiguity = crate::schedule::generate_ambiguity_policy(self.ambiguity);
+        let executor = crate::schedule::generate_executor(executor);
+
+        let run = quote! {
+            // Execute when clause
+            #actions
+
+            // Verify then clause
+            #assertions
+        };
+        let run = match self.world_dump.0 {
+            Some(limit) => {
+                let dump_world = crate::dump::generate_dump_world();
+                quote! {
+                    #dump_world
+
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| { #run }));
+                    if let Err(panic) = result {
+                        eprintln!("{}", dump_world(app.world(), #limit));
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+            }
+            None => run,
+        };
 
         quote! {
             #[test]
@@ -311,14 +397,13 @@ impl TestScenario {
                 // Create test app
                 let mut app = App::new();
                 app.add_plugins(MinimalPlugins);
+                #time_control
+                #ambiguity
+                #executor
 
                 #setup
 
-                // Execute when clause
-                #actions
-
-                // Verify then clause
-                #assertions
+                #run
             }
         }
     }
@@ -326,6 +411,20 @@ impl TestScenario {
     fn generate_setup(&self) -> TokenStream {
         let mut setup = TokenStream::new();
 
+        // Enter initial states
+        if !self.given.states.is_empty() {
+            setup.extend(quote! {
+                if !app.is_plugin_added::<bevy::state::app::StatesPlugin>() {
+                    app.add_plugins(bevy::state::app::StatesPlugin);
+                }
+            });
+        }
+        for state in &self.given.states {
+            setup.extend(quote! {
+                app.insert_state(#state);
+            });
+        }
+
         // Add resources
         for resource in &self.given.resources {
             setup.extend(quote! {
@@ -367,6 +466,13 @@ impl TestScenario {
             });
         }
 
+        // Run OnExit/OnEnter for the initial states before the scenario starts
+        if !self.given.states.is_empty() {
+            setup.extend(quote! {
+                app.world_mut().run_schedule(bevy::state::state::StateTransition);
+            });
+        }
+
         setup
     }
 
@@ -399,11 +505,10 @@ impl TestScenario {
                             });
                         }
                         TimeAdvance::Seconds(s) => {
-                            // Advance time by seconds
+                            // Advance time by seconds in whole fixed steps
+                            let frames = (*s as f64 / crate::time::DEFAULT_FIXED_DT).round() as u32;
                             actions.extend(quote! {
-                                // Advance by seconds - 60 FPS assumed
-                                let frames = (#s * 60.0) as usize;
-                                for _ in 0..frames {
+                                for _ in 0..#frames {
                                     app.update();
                                 }
                             });
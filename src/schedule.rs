@@ -0,0 +1,130 @@
+//! Schedule configuration for generated test apps
+//!
+//! Build settings that make schedule problems, such as system-order ambiguities,
+//! fail the test instead of only being logged.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse::Parse, parse::ParseStream, Result};
+
+/// How `ambiguity = ...` treats systems with conflicting access and no ordering
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum AmbiguityPolicy {
+    /// Bevy's default: ambiguities are not checked
+    #[default]
+    Allow,
+    /// Any ambiguity fails the test when its schedule is built
+    Deny,
+}
+
+impl Parse for AmbiguityPolicy {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "allow" => Ok(AmbiguityPolicy::Allow),
+            "deny" => Ok(AmbiguityPolicy::Deny),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "Unknown ambiguity policy, expected `allow` or `deny`",
+            )),
+        }
+    }
+}
+
+/// Which executor the schedules of a test app run on
+#[derive(Clone, Copy, PartialEq)]
+pub enum Executor {
+    SingleThreaded,
+    MultiThreaded,
+}
+
+impl Executor {
+    /// Parse `single_threaded`, `multi_threaded` or `both`
+    pub fn parse_list(input: ParseStream) -> Result<Vec<Executor>> {
+        let ident: syn::Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "single_threaded" => Ok(vec![Executor::SingleThreaded]),
+            "multi_threaded" => Ok(vec![Executor::MultiThreaded]),
+            "both" => Ok(vec![Executor::SingleThreaded, Executor::MultiThreaded]),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "Unknown executors, expected `single_threaded`, `multi_threaded` or `both`",
+            )),
+        }
+    }
+
+    /// Suffix of the test generated for this executor when a test runs on several
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Executor::SingleThreaded => "single_threaded",
+            Executor::MultiThreaded => "multi_threaded",
+        }
+    }
+}
+
+/// Run every schedule of `app` on `executor`, including schedules created later in the test.
+///
+/// `Main` and `FixedMain` only run the other schedules in order and stay single-threaded.
+pub fn generate_executor(executor: Option<Executor>) -> TokenStream {
+    let kind = match executor {
+        None => return quote! {},
+        Some(Executor::SingleThreaded) => quote! { SingleThreaded },
+        Some(Executor::MultiThreaded) => quote! { MultiThreaded },
+    };
+
+    configure_all_schedules(syn::parse_quote! {
+        fn configure_executor(world: &mut bevy::ecs::world::World) {
+            use bevy::ecs::schedule::ScheduleLabel;
+
+            let main = [bevy::app::Main.intern(), bevy::app::FixedMain.intern()];
+            let kind = bevy::ecs::schedule::ExecutorKind::#kind;
+            let mut schedules = world.resource_mut::<bevy::ecs::schedule::Schedules>();
+            for (_, schedule) in schedules.iter_mut() {
+                if !main.contains(&schedule.label()) && schedule.get_executor_kind() != kind {
+                    schedule.set_executor_kind(kind);
+                }
+            }
+        }
+    })
+}
+
+/// Apply `policy` to every schedule of `app`, including schedules created later in the test.
+///
+/// Denied ambiguities make the schedule panic when it is built, listing each conflicting
+/// system pair and the components or resources they both access.
+pub fn generate_ambiguity_policy(policy: AmbiguityPolicy) -> TokenStream {
+    match policy {
+        AmbiguityPolicy::Allow => quote! {},
+        AmbiguityPolicy::Deny => configure_all_schedules(syn::parse_quote! {
+            fn deny_ambiguities(world: &mut bevy::ecs::world::World) {
+                let mut schedules = world.resource_mut::<bevy::ecs::schedule::Schedules>();
+                for (_, schedule) in schedules.iter_mut() {
+                    let mut settings = schedule.get_build_settings();
+                    if settings.ambiguity_detection != bevy::ecs::schedule::LogLevel::Error {
+                        settings.ambiguity_detection = bevy::ecs::schedule::LogLevel::Error;
+                        schedule.set_build_settings(settings);
+                    }
+                }
+            }
+        }),
+    }
+}
+
+/// Call the exclusive system `configure` on `app` now and at the start of every frame.
+///
+/// Running it again catches schedules added after setup, e.g. `Startup` or `OnEnter`
+/// schedules created by the test body, before they are first built.
+pub fn configure_all_schedules(configure: syn::ItemFn) -> TokenStream {
+    let name = &configure.sig.ident;
+    quote! {
+        {
+            use bevy::ecs::schedule::IntoScheduleConfigs;
+
+            #configure
+
+            #name(app.world_mut());
+            app.add_systems(bevy::app::PreStartup, #name.ambiguous_with_all());
+            app.add_systems(bevy::app::First, #name.ambiguous_with_all());
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! World snapshots and diffs
+//!
+//! `TestApp::world_snapshot` records every entity with its components and reflected
+//! field values, and `TestApp::diff_since` reports what changed since then.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generate `WorldSnapshot`, `WorldDiff` and `FieldChange`; expects the output of
+/// `dump::generate_dump_world` alongside for `short_type_name`
+pub fn generate_world_snapshot() -> TokenStream {
+    quote! {
+        /// Entities, components and reflected field values of a world at one point in time
+        #[derive(Debug, Clone, Default)]
+        pub struct WorldSnapshot {
+            entities: std::collections::BTreeMap<bevy::ecs::entity::Entity, EntitySnapshot>,
+        }
+
+        #[derive(Debug, Clone)]
+        struct EntitySnapshot {
+            name: Option<String>,
+            /// Type name of each component, with its fields if it is reflected
+            components: std::collections::BTreeMap<String, Option<Vec<(String, String)>>>,
+        }
+
+        impl WorldSnapshot {
+            pub fn capture(world: &bevy::ecs::world::World) -> Self {
+                use bevy::reflect::{PartialReflect, ReflectRef};
+
+                let registry = world
+                    .get_resource::<bevy::ecs::reflect::AppTypeRegistry>()
+                    .map(|registry| registry.read());
+
+                let mut entities = std::collections::BTreeMap::new();
+                for entity in world.iter_entities() {
+                    let mut components = std::collections::BTreeMap::new();
+                    for info in world.inspect_entity(entity.id()).into_iter().flatten() {
+                        let value = registry.as_ref().zip(info.type_id()).and_then(|(registry, type_id)| {
+                            registry
+                                .get_type_data::<bevy::ecs::reflect::ReflectComponent>(type_id)?
+                                .reflect(entity)
+                        });
+                        let fields = value.map(|value| match value.reflect_ref() {
+                            ReflectRef::Struct(value) => (0..value.field_len())
+                                .map(|index| {
+                                    let name = value.name_at(index).unwrap_or_default().to_string();
+                                    (name, format!("{:?}", value.field_at(index).unwrap()))
+                                })
+                                .collect(),
+                            ReflectRef::TupleStruct(value) => value
+                                .iter_fields()
+                                .enumerate()
+                                .map(|(index, field)| (index.to_string(), format!("{:?}", field)))
+                                .collect(),
+                            _ => vec![(String::new(), format!("{:?}", value.as_partial_reflect()))],
+                        });
+                        components.insert(info.name().to_string(), fields);
+                    }
+                    let name = entity.get::<bevy::ecs::name::Name>().map(|name| name.to_string());
+                    entities.insert(entity.id(), EntitySnapshot { name, components });
+                }
+                WorldSnapshot { entities }
+            }
+
+            /// Everything that differs between this snapshot and the later `after`
+            pub fn diff(&self, after: &WorldSnapshot) -> WorldDiff {
+                let mut diff = WorldDiff::default();
+                for (&entity, snapshot) in &after.entities {
+                    let Some(before) = self.entities.get(&entity) else {
+                        diff.spawned.push(entity);
+                        continue;
+                    };
+                    for (component, fields) in &snapshot.components {
+                        let Some(old_fields) = before.components.get(component) else {
+                            diff.added.push((entity, component.clone()));
+                            continue;
+                        };
+                        let (Some(old_fields), Some(fields)) = (old_fields, fields) else {
+                            continue;
+                        };
+                        for ((field, old), (_, new)) in old_fields.iter().zip(fields) {
+                            if old != new {
+                                diff.changed.push(FieldChange {
+                                    entity,
+                                    component: component.clone(),
+                                    field: field.clone(),
+                                    before: old.clone(),
+                                    after: new.clone(),
+                                });
+                            }
+                        }
+                    }
+                    for component in before.components.keys() {
+                        if !snapshot.components.contains_key(component) {
+                            diff.removed.push((entity, component.clone()));
+                        }
+                    }
+                }
+                diff.despawned = self
+                    .entities
+                    .keys()
+                    .filter(|entity| !after.entities.contains_key(entity))
+                    .copied()
+                    .collect();
+                diff.names = self
+                    .entities
+                    .iter()
+                    .chain(&after.entities)
+                    .filter_map(|(&entity, snapshot)| Some((entity, snapshot.name.clone()?)))
+                    .collect();
+                diff
+            }
+        }
+
+        /// Changes between a `WorldSnapshot` and a later state of the world.
+        ///
+        /// Component names are full type names, as given by `std::any::type_name`.
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct WorldDiff {
+            pub spawned: Vec<bevy::ecs::entity::Entity>,
+            pub despawned: Vec<bevy::ecs::entity::Entity>,
+            /// Components inserted on entities that existed in the snapshot
+            pub added: Vec<(bevy::ecs::entity::Entity, String)>,
+            /// Components removed from entities that still exist
+            pub removed: Vec<(bevy::ecs::entity::Entity, String)>,
+            /// Reflected fields whose value changed
+            pub changed: Vec<FieldChange>,
+            names: std::collections::BTreeMap<bevy::ecs::entity::Entity, String>,
+        }
+
+        /// A reflected field whose `Debug` output changed
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct FieldChange {
+            pub entity: bevy::ecs::entity::Entity,
+            pub component: String,
+            /// Field name, tuple index, or empty for components that are not structs
+            pub field: String,
+            pub before: String,
+            pub after: String,
+        }
+
+        impl WorldDiff {
+            pub fn is_empty(&self) -> bool {
+                self.spawned.is_empty()
+                    && self.despawned.is_empty()
+                    && self.added.is_empty()
+                    && self.removed.is_empty()
+                    && self.changed.is_empty()
+            }
+
+            /// This diff without the component changes to the given component types
+            pub fn except(&self, components: &[&str]) -> WorldDiff {
+                let kept = |component: &String| !components.contains(&component.as_str());
+                WorldDiff {
+                    added: self.added.iter().filter(|(_, component)| kept(component)).cloned().collect(),
+                    removed: self.removed.iter().filter(|(_, component)| kept(component)).cloned().collect(),
+                    changed: self.changed.iter().filter(|change| kept(&change.component)).cloned().collect(),
+                    ..self.clone()
+                }
+            }
+
+            fn entity_label(&self, entity: bevy::ecs::entity::Entity) -> String {
+                match self.names.get(&entity) {
+                    Some(name) => format!("{} {:?}", entity, name),
+                    None => entity.to_string(),
+                }
+            }
+        }
+
+        impl std::fmt::Display for WorldDiff {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                if self.is_empty() {
+                    return write!(f, "World unchanged");
+                }
+                write!(f, "World changes:")?;
+                for &entity in &self.spawned {
+                    write!(f, "\n  spawned {}", self.entity_label(entity))?;
+                }
+                for &entity in &self.despawned {
+                    write!(f, "\n  despawned {}", self.entity_label(entity))?;
+                }
+                for (entity, component) in &self.added {
+                    write!(f, "\n  {}: added {}", self.entity_label(*entity), short_type_name(component))?;
+                }
+                for (entity, component) in &self.removed {
+                    write!(f, "\n  {}: removed {}", self.entity_label(*entity), short_type_name(component))?;
+                }
+                for change in &self.changed {
+                    let mut path = short_type_name(&change.component);
+                    if !change.field.is_empty() {
+                        path.push('.');
+                        path.push_str(&change.field);
+                    }
+                    write!(
+                        f,
+                        "\n  {}: {}: {} -> {}",
+                        self.entity_label(change.entity),
+                        path,
+                        change.before,
+                        change.after
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -167,6 +167,10 @@ impl SystemTest {
         let setup = self.generate_setup();
         let system_call = self.generate_system_call();
         let assertions = self.generate_assertions();
+        let time_control = crate::time::generate_time_control(
+            crate::time::step_nanos(crate::time::DEFAULT_FIXED_DT),
+            quote! { false },
+        );
 
         quote! {
             #[test]
@@ -176,6 +180,7 @@ impl SystemTest {
                 // Create test app with minimal setup
                 let mut app = App::new();
                 app.add_plugins(MinimalPlugins);
+                #time_control
 
                 // Setup phase
                 #setup
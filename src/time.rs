@@ -0,0 +1,140 @@
+//! Deterministic time control
+//!
+//! Every generated test app advances its clocks by a fixed step per update instead
+//! of by wall-clock time, so time-dependent tests behave the same on a loaded CI
+//! runner as on a developer machine.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, Result};
+
+/// Step used when a test does not configure one, matching the 60 FPS the macros assume
+pub const DEFAULT_FIXED_DT: f64 = 1.0 / 60.0;
+
+/// Evaluate a step such as `1/30`, `0.02` or `(1.0 / 60.0)` to seconds at compile time
+pub fn parse_seconds(expr: &Expr) -> Result<f64> {
+    let seconds = match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(n),
+            ..
+        }) => n.base10_parse::<f64>()?,
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Float(f),
+            ..
+        }) => f.base10_parse::<f64>()?,
+        Expr::Paren(paren) => parse_seconds(&paren.expr)?,
+        Expr::Group(group) => parse_seconds(&group.expr)?,
+        Expr::Binary(binary) if matches!(binary.op, syn::BinOp::Div(_)) => {
+            parse_seconds(&binary.left)? / parse_seconds(&binary.right)?
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                expr,
+                "Expected a step in seconds such as `1/30` or `0.02`",
+            ))
+        }
+    };
+
+    if seconds.is_finite() && seconds > 0.0 {
+        Ok(seconds)
+    } else {
+        Err(syn::Error::new_spanned(
+            expr,
+            "Step must be a positive number of seconds",
+        ))
+    }
+}
+
+/// Generate an `f64` expression for a step in seconds, in nanoseconds
+pub fn step_nanos(seconds: f64) -> TokenStream {
+    let nanos = seconds * 1e9;
+    quote! { #nanos }
+}
+
+/// Switch `app` to manual time that advances by one `step_nanos` step per update.
+///
+/// A step is rarely a whole number of nanoseconds, so updates alternate between the
+/// step rounded down and up such that `n` updates always take `n` steps rounded once,
+/// e.g. 60 updates at 1/60 s take exactly one second. `Time`, `Time<Virtual>` and
+/// `Time<Fixed>` all move in lockstep, with one fixed tick per update. A fixed timestep
+/// that a plugin already changed from Bevy's default is kept unless `override_fixed`
+/// is true, so tests run gameplay at the game's own fixed rate. Apps without the
+/// `TimePlugin` only get the update strategy.
+///
+/// Running the generated code again on the same app only changes the step.
+pub fn generate_time_control(step_nanos: TokenStream, override_fixed: TokenStream) -> TokenStream {
+    quote! {
+        {
+            use bevy::ecs::schedule::IntoScheduleConfigs;
+            use bevy::time::{Fixed, Real, Time, TimeUpdateStrategy, Virtual};
+
+            /// Real time per update and the number of full updates run at that rate
+            #[derive(bevy::ecs::resource::Resource)]
+            struct ManualTimeStep {
+                nanos: f64,
+                updates: u64,
+                owns_fixed: bool,
+            }
+
+            impl ManualTimeStep {
+                /// Length of the next full update
+                fn delta(&self) -> std::time::Duration {
+                    let at = |updates: u64| (updates as f64 * self.nanos).round() as u64;
+                    std::time::Duration::from_nanos(at(self.updates + 1) - at(self.updates))
+                }
+
+                /// Set up the clocks for the next update
+                fn prepare(&self, world: &mut bevy::ecs::world::World) {
+                    let delta = self.delta();
+                    world.insert_resource(TimeUpdateStrategy::ManualDuration(delta));
+                    if self.owns_fixed {
+                        if let Some(mut fixed) = world.get_resource_mut::<Time<Fixed>>() {
+                            fixed.set_timestep(delta);
+                        }
+                    }
+                }
+
+                fn next_update(world: &mut bevy::ecs::world::World) {
+                    let Some(delta) = world.get_resource::<ManualTimeStep>().map(ManualTimeStep::delta) else {
+                        return;
+                    };
+                    // Updates shortened by the test itself are not steps, other strategies
+                    // are left alone
+                    match world.get_resource::<TimeUpdateStrategy>() {
+                        Some(TimeUpdateStrategy::ManualDuration(used)) if *used == delta => {
+                            world.resource_mut::<ManualTimeStep>().updates += 1;
+                        }
+                        Some(TimeUpdateStrategy::ManualDuration(_)) => {}
+                        _ => return,
+                    }
+                    world.resource_scope(|world, step: bevy::ecs::world::Mut<ManualTimeStep>| step.prepare(world));
+                }
+            }
+
+            let nanos: f64 = #step_nanos;
+            if !app.world().contains_resource::<ManualTimeStep>() {
+                app.add_systems(bevy::app::Last, ManualTimeStep::next_update.ambiguous_with_all());
+            }
+
+            let world = app.world_mut();
+            if let Some(mut real) = world.get_resource_mut::<Time<Real>>() {
+                if real.last_update().is_none() {
+                    // Start the clock now so the very first update already advances by a step
+                    let startup = real.startup();
+                    real.update_with_instant(startup);
+                }
+            }
+            if let Some(mut virtual_time) = world.get_resource_mut::<Time<Virtual>>() {
+                let max_delta = virtual_time.max_delta().max(std::time::Duration::from_nanos(nanos.ceil() as u64));
+                virtual_time.set_max_delta(max_delta);
+            }
+            let owns_fixed = world.get_resource::<Time<Fixed>>().is_some_and(|fixed| {
+                #override_fixed || fixed.timestep() == Time::<Fixed>::default().timestep()
+            });
+
+            let step = ManualTimeStep { nanos, updates: 0, owns_fixed };
+            step.prepare(world);
+            world.insert_resource(step);
+        }
+    }
+}
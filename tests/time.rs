@@ -0,0 +1,53 @@
+//! Virtual time advances by exactly the requested amount
+
+use bevy::prelude::*;
+use bevy_test_suite::*;
+use std::time::Duration;
+
+bevy_test_utils!();
+
+fn virtual_elapsed(app: &App) -> Duration {
+    app.world().resource::<Time<Virtual>>().elapsed()
+}
+
+#[bevy_test]
+fn advance_time_is_exact(app: &mut App) {
+    app.advance_time(0.25);
+    assert_eq!(virtual_elapsed(app), Duration::from_millis(250));
+    app.advance_time(1.0);
+    assert_eq!(virtual_elapsed(app), Duration::from_millis(1250));
+    app.advance_time(0.01);
+    assert_eq!(virtual_elapsed(app), Duration::from_millis(1260));
+}
+
+#[bevy_test]
+fn advance_time_is_exact_at_double_speed(app: &mut App) {
+    app.set_time_speed(2.0);
+    app.advance_time(0.5);
+    assert_eq!(virtual_elapsed(app), Duration::from_millis(500));
+}
+
+#[derive(Resource, Default)]
+struct FixedTicks(u32);
+
+fn count_fixed_ticks(mut ticks: ResMut<FixedTicks>) {
+    ticks.0 += 1;
+}
+
+#[bevy_test(fixed_dt = 1/30)]
+fn every_clock_advances_by_whole_seconds(app: &mut App) {
+    app.init_resource::<FixedTicks>();
+    app.add_systems(FixedUpdate, count_fixed_ticks);
+
+    app.advance_time(1.0);
+    assert_eq!(app.world().resource::<FixedTicks>().0, 30);
+    assert_eq!(virtual_elapsed(app), Duration::from_secs(1));
+    assert_eq!(
+        app.world().resource::<Time<Fixed>>().elapsed(),
+        Duration::from_secs(1)
+    );
+    assert_eq!(
+        app.world().resource::<Time>().elapsed(),
+        Duration::from_secs(1)
+    );
+}
@@ -0,0 +1,45 @@
+//! A test that times out must not take a thread of Bevy's shared task pools with it
+
+use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, TaskPoolBuilder};
+use bevy_test_suite::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+bevy_test_utils!();
+
+static SPINNING: AtomicBool = AtomicBool::new(false);
+
+/// Give both tests a single compute thread, as on a one-CPU runner
+fn single_thread_pool() {
+    ComputeTaskPool::get_or_init(|| TaskPoolBuilder::new().num_threads(1).build());
+}
+
+fn spin_forever() {
+    SPINNING.store(true, Ordering::SeqCst);
+    loop {
+        std::hint::spin_loop();
+    }
+}
+
+#[bevy_test(profile = ecs_only, timeout = 1000)]
+#[should_panic(expected = "exceeded its timeout")]
+fn hung_system_times_out(app: &mut App) {
+    single_thread_pool();
+    app.add_systems(Update, spin_forever);
+    app.update();
+}
+
+#[bevy_test(profile = ecs_only, executors = multi_threaded)]
+fn later_tests_still_update(app: &mut App) {
+    single_thread_pool();
+
+    // Wait until the other test's system is stuck, if that test runs at all
+    let start = Instant::now();
+    while !SPINNING.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(10) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    app.add_systems(Update, || {});
+    app.update();
+}